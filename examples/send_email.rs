@@ -1,10 +1,9 @@
 extern crate socketlabs;
 
 use std::collections::HashMap;
-use std::env;
 
+use socketlabs::client::SocketLabsClient;
 use socketlabs::message::Message;
-use socketlabs::request::Request;
 
 fn main() {
     let mut message = Message::new("foo@bar.com", None);
@@ -16,16 +15,7 @@ fn main() {
     headers.insert("x-example", "hey hey hey");
     message.add_headers(headers);
 
-    let request = Request::new(
-        env::var("SOCKETLABS_SERVER_ID")
-            .expect("No SOCKERLABS_SERVER_ID environment variable set.")
-            .parse()
-            .expect("Error parsing SOCKERLABS_SERVER_ID."),
-        env::var("SOCKETLABS_API_KEY")
-            .expect("No SOCKERLABS_API_KEY environment variable set.")
-            .to_string(),
-        vec![message],
-    ).unwrap();
+    let client = SocketLabsClient::from_env().unwrap();
 
-    println!("{:#?}", request.send().unwrap());
+    println!("{:#?}", client.send(&[message]).unwrap());
 }
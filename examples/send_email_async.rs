@@ -0,0 +1,14 @@
+use socketlabs::client::SocketLabsClient;
+use socketlabs::message::Message;
+
+#[tokio::main]
+async fn main() {
+    let mut message = Message::new("foo@bar.com", None);
+    message.add_to("bar@foo.com", None);
+    message.set_subject("Hello from the socketlabs-rs async example");
+    message.set_text("Hello, text world!");
+
+    let client = SocketLabsClient::from_env().unwrap();
+
+    println!("{:#?}", client.send_async(&[message]).await.unwrap());
+}
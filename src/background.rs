@@ -0,0 +1,197 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A worker sending messages in the background.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+use futures_util::{FutureExt, Sink};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_util::sync::PollSender;
+
+use crate::client::SocketLabsClient;
+use crate::error::{Error, ErrorKind, Result};
+use crate::message::{Message, OwnedMessage};
+use crate::request::MAX_MESSAGES_PER_REQUEST;
+
+/// How many messages may be queued by default.
+pub const DEFAULT_CAPACITY: usize = 10 * MAX_MESSAGES_PER_REQUEST;
+
+/// What the worker is asked to do.
+#[derive(Debug)]
+enum Command {
+    Send(Box<OwnedMessage>),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Sends enqueued messages from a tokio task, batching the ones
+/// enqueued together in as few injection requests as possible.
+///
+/// Requests are rate limited and retried as configured on the
+/// client. Messages which could not be sent are only handed to
+/// its dead letter sink, see `SocketLabsClient::set_dead_letter_sink`.
+///
+/// The sender is also a `Sink` of messages, so a stream can be
+/// forwarded into it. Flushing the sink waits for every message
+/// to be sent, and enqueueing waits while the queue is full.
+#[derive(Debug)]
+pub struct BackgroundSender {
+    commands: mpsc::Sender<Command>,
+    /// The same channel as `commands`, for the `Sink` implementation.
+    sink: PollSender<Command>,
+    /// Signals the end of a flush started by `Sink::poll_flush`.
+    flushed: Option<oneshot::Receiver<()>>,
+    worker: JoinHandle<()>,
+}
+
+impl BackgroundSender {
+    /// Spawns the worker on the current tokio runtime, queueing
+    /// up to `DEFAULT_CAPACITY` messages.
+    ///
+    /// # Panics
+    ///
+    /// When called outside of a tokio runtime.
+    pub fn new<C: Into<Arc<SocketLabsClient>>>(client: C) -> BackgroundSender {
+        BackgroundSender::with_capacity(client, DEFAULT_CAPACITY)
+    }
+
+    /// Spawns the worker on the current tokio
+    /// runtime, queueing up to `capacity` messages.
+    ///
+    /// # Panics
+    ///
+    /// When called outside of a tokio runtime.
+    pub fn with_capacity<C: Into<Arc<SocketLabsClient>>>(
+        client: C,
+        capacity: usize,
+    ) -> BackgroundSender {
+        let (commands, receiver) = mpsc::channel(capacity.max(1));
+        BackgroundSender {
+            sink: PollSender::new(commands.clone()),
+            commands,
+            flushed: None,
+            worker: tokio::spawn(run(client.into(), receiver)),
+        }
+    }
+
+    /// Queues a message to be sent in the background,
+    /// waiting while the queue is full.
+    pub async fn enqueue(&self, message: Message<'_>) -> Result<()> {
+        self.commands
+            .send(Command::Send(Box::new(message.into_owned())))
+            .await
+            .map_err(|_| ErrorKind::SenderClosed.into())
+    }
+
+    /// Waits until every message enqueued so far was sent.
+    pub async fn flush(&self) -> Result<()> {
+        let (done, flushed) = oneshot::channel();
+        self.commands
+            .send(Command::Flush(done))
+            .await
+            .map_err(|_| ErrorKind::SenderClosed)?;
+
+        flushed.await.map_err(|_| ErrorKind::SenderClosed.into())
+    }
+
+    /// Stops accepting messages, sends the ones still
+    /// queued and waits for the worker to finish.
+    pub async fn shutdown(self) -> Result<()> {
+        drop(self.commands);
+        drop(self.sink);
+        self.worker
+            .await
+            .map_err(|_| ErrorKind::UnexpectedError.into())
+    }
+}
+
+impl<'a> Sink<Message<'a>> for BackgroundSender {
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        self.sink
+            .poll_reserve(cx)
+            .map_err(|_| ErrorKind::SenderClosed.into())
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, message: Message<'a>) -> Result<()> {
+        self.sink
+            .send_item(Command::Send(Box::new(message.into_owned())))
+            .map_err(|_| ErrorKind::SenderClosed.into())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        if self.flushed.is_none() {
+            ready!(self.as_mut().poll_ready(cx))?;
+            let (done, flushed) = oneshot::channel();
+            self.sink
+                .send_item(Command::Flush(done))
+                .map_err(|_| ErrorKind::SenderClosed)?;
+            self.flushed = Some(flushed);
+        }
+
+        let result = match self.flushed {
+            Some(ref mut flushed) => ready!(flushed.poll_unpin(cx)),
+            None => Ok(()),
+        };
+        self.flushed = None;
+        Poll::Ready(result.map_err(|_| ErrorKind::SenderClosed.into()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        self.sink.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Receives commands until every sender is dropped.
+async fn run(client: Arc<SocketLabsClient>, mut receiver: mpsc::Receiver<Command>) {
+    let mut batch = Vec::new();
+    while let Some(command) = receiver.recv().await {
+        // Commands already waiting are handled right
+        // away, so their messages are sent together.
+        let mut next = Some(command);
+        while let Some(command) = next {
+            match command {
+                Command::Send(message) => {
+                    batch.push(*message);
+                    if batch.len() >= MAX_MESSAGES_PER_REQUEST {
+                        send(&client, &mut batch).await;
+                    }
+                }
+                Command::Flush(done) => {
+                    send(&client, &mut batch).await;
+                    let _ = done.send(());
+                }
+            }
+            next = receiver.try_recv().ok();
+        }
+        send(&client, &mut batch).await;
+    }
+}
+
+/// Sends a batch of messages, emptying it.
+async fn send(client: &SocketLabsClient, batch: &mut Vec<OwnedMessage>) {
+    if batch.is_empty() {
+        return;
+    }
+
+    match client.send_async(batch).await {
+        Ok(_) => {
+            log_debug!("Sent {} queued message(s)", batch.len());
+        }
+        Err(_error) => {
+            log_debug!(
+                "Failed to send {} queued message(s): {}",
+                batch.len(),
+                _error
+            );
+        }
+    }
+    batch.clear();
+}
@@ -0,0 +1,86 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A circuit breaker which stops sending to SocketLabs
+//! for a while after too many consecutive failures.
+
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use crate::error::{ErrorKind, Result};
+use crate::response::Response;
+use crate::retry::is_transient;
+
+/// A circuit breaker, opening after `failure_threshold` consecutive
+/// injection requests failed with a transient error, such as during a
+/// SocketLabs outage. While open, sending fails right away with
+/// `ErrorKind::CircuitOpen` instead of waiting on the endpoint.
+///
+/// Once `cool_down` elapsed, requests are let through again. The first
+/// one succeeding closes the circuit, failing reopens it right away.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cool_down: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// How many injection requests failed in a row.
+    failures: u32,
+    /// When the circuit was last opened, if it is open.
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Creates a new, closed, circuit breaker.
+    pub fn new(failure_threshold: u32, cool_down: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            failure_threshold: failure_threshold.max(1),
+            cool_down,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Whether sending fails right away.
+    pub fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(|error| error.into_inner());
+        state
+            .opened_at
+            .is_some_and(|opened_at| opened_at.elapsed() < self.cool_down)
+    }
+
+    /// Fails while the circuit is open.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.is_open() {
+            return Err(ErrorKind::CircuitOpen.into());
+        }
+
+        Ok(())
+    }
+
+    /// Records the result of an injection request.
+    pub(crate) fn record(&self, result: &Result<Response<'static>>) {
+        let mut state = self.state.lock().unwrap_or_else(|error| error.into_inner());
+        if !is_transient(result) {
+            *state = State::default();
+            return;
+        }
+
+        state.failures = state.failures.saturating_add(1);
+        if state.failures >= self.failure_threshold {
+            log_debug!(
+                "Opening the circuit after {} consecutive failures",
+                state.failures
+            );
+            state.opened_at = Some(Instant::now());
+        }
+    }
+}
@@ -0,0 +1,225 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A builder for `Message` which checks at compile time that
+//! the sender, a recipient, the subject and a body were set.
+//!
+//! ```
+//! use socketlabs::builder::MessageBuilder;
+//!
+//! let message = MessageBuilder::new()
+//!     .from("foo@bar.com", None)
+//!     .to("bar@foo.com", None)
+//!     .subject("Hello")
+//!     .text("Hello, world!")
+//!     .build();
+//! ```
+//!
+//! Leaving out any of them makes `build` unavailable:
+//!
+//! ```compile_fail
+//! use socketlabs::builder::MessageBuilder;
+//!
+//! let message = MessageBuilder::new()
+//!     .from("foo@bar.com", None)
+//!     .subject("Hello")
+//!     .text("Hello, world!")
+//!     .build();
+//! ```
+//!
+//! The body and the api template share the same slot, so
+//! setting both does not compile either:
+//!
+//! ```compile_fail
+//! use socketlabs::builder::MessageBuilder;
+//!
+//! let message = MessageBuilder::new()
+//!     .from("foo@bar.com", None)
+//!     .to("bar@foo.com", None)
+//!     .subject("Hello")
+//!     .text("Hello, world!")
+//!     .api_template("1")
+//!     .build();
+//! ```
+
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+use crate::message::Message;
+
+/// Marks a required field that was not set yet.
+#[derive(Debug)]
+pub struct Missing;
+
+/// Marks a required field that was set.
+#[derive(Debug)]
+pub struct Set;
+
+/// Marks a body replaced by an api template.
+#[derive(Debug)]
+pub struct Template;
+
+/// The states of the body slot where a text or html body
+/// may be set, that is any but `Template`.
+pub trait NoTemplate {}
+
+impl NoTemplate for Missing {}
+impl NoTemplate for Set {}
+
+/// The states of the body slot allowing to build
+/// the message, that is a body or an api template.
+pub trait HasBody {}
+
+impl HasBody for Set {}
+impl HasBody for Template {}
+
+/// A `Message` builder whose type parameters track whether
+/// the sender, a recipient, the subject and a body, or
+/// an api template in its place, were set.
+#[derive(Debug)]
+pub struct MessageBuilder<'a, From, To, Subject, Body> {
+    message: Message<'a>,
+    state: PhantomData<(From, To, Subject, Body)>,
+}
+
+impl<'a> MessageBuilder<'a, Missing, Missing, Missing, Missing> {
+    /// Creates a new builder with no fields set.
+    pub fn new() -> Self {
+        MessageBuilder {
+            message: Message::new("", None),
+            state: PhantomData,
+        }
+    }
+}
+
+impl<'a> Default for MessageBuilder<'a, Missing, Missing, Missing, Missing> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, F, T, S, B> MessageBuilder<'a, F, T, S, B> {
+    /// Moves the message into a builder with a different state.
+    fn transition<F2, T2, S2, B2>(self) -> MessageBuilder<'a, F2, T2, S2, B2> {
+        MessageBuilder {
+            message: self.message,
+            state: PhantomData,
+        }
+    }
+
+    /// Sets the sender. See `Message::set_from`.
+    pub fn from<A: Into<Cow<'a, str>>>(
+        mut self,
+        address: A,
+        name: Option<A>,
+    ) -> MessageBuilder<'a, Set, T, S, B> {
+        self.message.set_from(address, name);
+        self.transition()
+    }
+
+    /// Adds a new recipient. See `Message::add_to`.
+    pub fn to<A: Into<Cow<'a, str>>>(
+        mut self,
+        address: A,
+        name: Option<A>,
+    ) -> MessageBuilder<'a, F, Set, S, B> {
+        self.message.add_to(address, name);
+        self.transition()
+    }
+
+    /// Sets the subject. See `Message::set_subject`.
+    pub fn subject<A: Into<Cow<'a, str>>>(
+        mut self,
+        subject: A,
+    ) -> MessageBuilder<'a, F, T, Set, B> {
+        self.message.set_subject(subject);
+        self.transition()
+    }
+
+    /// Adds a new cc'd recipient. See `Message::add_cc`.
+    pub fn cc<A: Into<Cow<'a, str>>>(mut self, address: A, name: Option<A>) -> Self {
+        self.message.add_cc(address, name);
+        self
+    }
+
+    /// Adds a new bcc'd recipient. See `Message::add_bcc`.
+    pub fn bcc<A: Into<Cow<'a, str>>>(mut self, address: A, name: Option<A>) -> Self {
+        self.message.add_bcc(address, name);
+        self
+    }
+
+    /// Sets the reply to address. See `Message::set_reply_to`.
+    pub fn reply_to<A: Into<Cow<'a, str>>>(mut self, address: A, name: Option<A>) -> Self {
+        self.message.set_reply_to(address, name);
+        self
+    }
+
+    /// Sets the message id. See `Message::set_message_id`.
+    pub fn message_id<A: Into<Cow<'a, str>>>(mut self, message_id: A) -> Self {
+        self.message.set_message_id(message_id);
+        self
+    }
+
+    /// Sets the charset. See `Message::set_charset`.
+    pub fn charset<A: Into<Cow<'a, str>>>(mut self, charset: A) -> Self {
+        self.message.set_charset(charset);
+        self
+    }
+
+    /// Adds custom headers. See `Message::add_headers`.
+    pub fn headers<K, V, I>(mut self, headers: I) -> Self
+    where
+        K: Into<Cow<'a, str>>,
+        V: Into<Cow<'a, str>>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.message.add_headers(headers);
+        self
+    }
+
+    /// Adds a new attachment. See `Message::add_attachment`.
+    pub fn attachment<N: Into<Cow<'a, str>>, M: Into<Cow<'a, str>>, C: Into<Vec<u8>>>(
+        mut self,
+        name: N,
+        content_type: M,
+        content: C,
+    ) -> Self {
+        self.message.add_attachment(name, content_type, content);
+        self
+    }
+}
+
+impl<'a, F, T, S, B: NoTemplate> MessageBuilder<'a, F, T, S, B> {
+    /// Sets the text body. See `Message::set_text`.
+    pub fn text<A: Into<Cow<'a, str>>>(mut self, text: A) -> MessageBuilder<'a, F, T, S, Set> {
+        self.message.set_text(text);
+        self.transition()
+    }
+
+    /// Sets the html body. See `Message::set_html`.
+    pub fn html<A: Into<Cow<'a, str>>>(mut self, html: A) -> MessageBuilder<'a, F, T, S, Set> {
+        self.message.set_html(html);
+        self.transition()
+    }
+}
+
+impl<'a, F, T, S> MessageBuilder<'a, F, T, S, Missing> {
+    /// Sets the api template, which takes the place of
+    /// the body. See `Message::set_api_template`.
+    pub fn api_template<A: Into<Cow<'a, str>>>(
+        mut self,
+        api_template: A,
+    ) -> MessageBuilder<'a, F, T, S, Template> {
+        self.message.set_api_template(api_template);
+        self.transition()
+    }
+}
+
+impl<'a, B: HasBody> MessageBuilder<'a, Set, Set, Set, B> {
+    /// Builds the message. Only available once the sender, a
+    /// recipient, the subject and a body or api template were set.
+    pub fn build(self) -> Message<'a> {
+        self.message
+    }
+}
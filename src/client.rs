@@ -0,0 +1,713 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A reusable client for
+//! the SocketLabs [Injection API](https://www.socketlabs.com/api-reference/injection-api/).
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+use std::ops::Range;
+use std::sync::Arc;
+#[cfg(feature = "blocking")]
+use std::thread;
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use bytes::Bytes;
+#[cfg(feature = "async")]
+use futures_util::stream::{self, Stream, StreamExt};
+#[cfg(feature = "async")]
+use futures_util::FutureExt;
+#[cfg(all(feature = "blocking", feature = "reqwest"))]
+use reqwest::blocking;
+#[cfg(all(feature = "async", feature = "reqwest"))]
+use reqwest::Client;
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::breaker::CircuitBreaker;
+use crate::credentials::{Credentials, CredentialsProvider, EnvCredentials};
+use crate::dead_letter::DeadLetterSink;
+#[cfg(feature = "async")]
+use crate::error::Error;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::error::ErrorKind;
+use crate::error::Result;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::limiter::RateLimiter;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::message::Message;
+#[cfg(feature = "async")]
+use crate::request::post;
+#[cfg(feature = "blocking")]
+use crate::request::post_blocking;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::request::{injection_bodies, validate_messages, MAX_MESSAGES_PER_REQUEST};
+#[cfg(feature = "async")]
+use crate::response::MessageOutcome;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::response::{MessageResultErrorCode, PostMessageErrorCode, Response};
+#[cfg(feature = "blocking")]
+use crate::retry::retry_blocking;
+use crate::retry::SendOptions;
+#[cfg(feature = "async")]
+use crate::retry::{retry, sleep};
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::transport::DryRun;
+#[cfg(feature = "reqwest")]
+use crate::transport::ReqwestTransport;
+#[cfg(not(feature = "reqwest"))]
+use crate::transport::UreqTransport;
+use crate::transport::{
+    check_base_url, env_base_url, ApiVersion, HttpOptions, TlsVersion, Transport,
+};
+
+/// Whether a client actually sends messages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClientMode {
+    /// Messages are sent to SocketLabs.
+    #[default]
+    Live,
+    /// Messages are validated and serialized, and the payload is
+    /// logged with the `logging` feature, but nothing is sent.
+    /// Every injection request gets a synthetic `Success` response.
+    DryRun,
+}
+
+/// This is a client that holds the
+/// Injection API credentials and can be used
+/// to send any number of messages.
+///
+/// With the default transport, HTTP connections are pooled and
+/// reused across sends, so a single client should be created and shared.
+#[derive(Debug)]
+pub struct SocketLabsClient {
+    /// Swapped as a whole, so a send never mixes two credentials.
+    credentials: ArcSwap<Credentials>,
+    /// Looks up the credentials on every send instead.
+    credentials_provider: Option<Box<dyn CredentialsProvider>>,
+    /// Sends injection requests over HTTP.
+    transport: Box<dyn Transport>,
+    /// Whether messages are actually sent.
+    mode: ClientMode,
+    /// Whether to validate every message before sending.
+    validate: bool,
+    /// How many injection requests `send_async` may have in flight.
+    max_in_flight: usize,
+    /// How failed injection requests are retried.
+    options: SendOptions,
+    /// Fails fast while SocketLabs keeps failing.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    circuit_breaker: Option<CircuitBreaker>,
+    /// Throttles injection requests before they are sent.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    rate_limiter: Option<RateLimiter>,
+    /// Receives the messages which could not be sent.
+    dead_letter_sink: Option<Box<dyn DeadLetterSink>>,
+}
+
+/// A builder for `SocketLabsClient`, to configure
+/// how it connects to SocketLabs.
+///
+/// ```
+/// use socketlabs::client::SocketLabsClient;
+///
+/// let client = SocketLabsClient::builder(1234, "api-key")
+///     .base_url("http://localhost:8080")
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct ClientBuilder {
+    credentials: Credentials,
+    base_url: Option<String>,
+    api_version: ApiVersion,
+    http_options: HttpOptions,
+    validate: bool,
+    #[cfg(all(feature = "async", feature = "reqwest"))]
+    http_client: Option<Client>,
+    #[cfg(all(feature = "blocking", feature = "reqwest"))]
+    blocking_http_client: Option<blocking::Client>,
+}
+
+impl ClientBuilder {
+    /// Sets the base URL of the Injection API, such as a gateway or a
+    /// local mock server. Defaults to the one from the
+    /// `transport::BASE_URL_VAR` environment variable if set, or
+    /// `transport::DEFAULT_BASE_URL` otherwise.
+    pub fn base_url<T: Into<String>>(mut self, base_url: T) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Sets the revision of the injection endpoint
+    /// messages are sent to. Defaults to `ApiVersion::V1`.
+    pub fn api_version(mut self, api_version: ApiVersion) -> Self {
+        self.api_version = api_version;
+        self
+    }
+
+    /// Sends every request through the proxy at `proxy`, instead
+    /// of the ones from the `HTTPS_PROXY`, `HTTP_PROXY` and
+    /// `ALL_PROXY` environment variables.
+    pub fn proxy<T: Into<String>>(mut self, proxy: T) -> Self {
+        self.http_options.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Ignores the proxies from the environment.
+    pub fn no_proxy(mut self) -> Self {
+        self.http_options.no_proxy = true;
+        self
+    }
+
+    /// Trusts the PEM encoded certificates in `pem` on top
+    /// of the system ones, such as a corporate CA.
+    pub fn add_root_certificate<T: Into<Vec<u8>>>(mut self, pem: T) -> Self {
+        self.http_options.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Sets the lowest version of TLS accepted.
+    pub fn min_tls_version(mut self, min_tls_version: TlsVersion) -> Self {
+        self.http_options.min_tls_version = Some(min_tls_version);
+        self
+    }
+
+    /// Sets how long connecting to SocketLabs may take.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.http_options.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Sets how long every injection request may take as a whole,
+    /// retries aside. Requests taking longer fail with `ErrorKind::Timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.http_options.timeout = Some(timeout);
+        self
+    }
+
+    /// Sends requests over HTTP/2 right away, without negotiating
+    /// the version of HTTP, which saves a round trip per connection.
+    pub fn http2_prior_knowledge(mut self) -> Self {
+        self.http_options.http2_prior_knowledge = true;
+        self
+    }
+
+    /// Sets how long an idle connection is kept open for reuse.
+    pub fn pool_idle_timeout(mut self, pool_idle_timeout: Duration) -> Self {
+        self.http_options.pool_idle_timeout = Some(pool_idle_timeout);
+        self
+    }
+
+    /// Sets how many idle connections are kept open for reuse.
+    pub fn pool_max_idle_per_host(mut self, pool_max_idle_per_host: usize) -> Self {
+        self.http_options.pool_max_idle_per_host = Some(pool_max_idle_per_host);
+        self
+    }
+
+    /// Sends TCP keep-alive probes every `interval` on open
+    /// connections, so idle ones aren't dropped along the way.
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.http_options.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Adds an HTTP header sent with every injection request, such as a
+    /// correlation id or one an egress gateway requires. These aren't
+    /// email headers, see `message::Message::add_header` for those.
+    pub fn http_header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.http_options.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sends request bodies larger than `threshold` bytes gzip
+    /// compressed, such as large batches of merge data.
+    #[cfg(feature = "gzip")]
+    pub fn gzip_threshold(mut self, threshold: usize) -> Self {
+        self.http_options.gzip_threshold = Some(threshold);
+        self
+    }
+
+    /// Sends asynchronous requests with an existing `reqwest` client,
+    /// such as one shared with the rest of an application, instead of
+    /// one built by the client. The proxy, TLS, timeout and connection
+    /// options of the builder don't apply to it.
+    #[cfg(all(feature = "async", feature = "reqwest"))]
+    pub fn http_client(mut self, http_client: Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
+    /// Sends blocking requests with an existing `reqwest` blocking
+    /// client, instead of one built by the client. The proxy, TLS,
+    /// timeout and connection options of the builder don't apply to it.
+    #[cfg(all(feature = "blocking", feature = "reqwest"))]
+    pub fn blocking_http_client(mut self, blocking_http_client: blocking::Client) -> Self {
+        self.blocking_http_client = Some(blocking_http_client);
+        self
+    }
+
+    /// Sets whether every message is checked with `Message::validate`
+    /// before sending, failing locally instead of at SocketLabs.
+    /// See `SocketLabsClient::set_validate`.
+    pub fn validate(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    /// Builds the client, failing on an invalid
+    /// base URL, proxy or root certificate.
+    pub fn build(self) -> Result<SocketLabsClient> {
+        let base_url = match self.base_url {
+            Some(base_url) => {
+                check_base_url(&base_url)?;
+                base_url
+            }
+            None => env_base_url(),
+        };
+        #[cfg(feature = "reqwest")]
+        let mut transport = ReqwestTransport::with_options(base_url, self.http_options)?;
+        #[cfg(not(feature = "reqwest"))]
+        let mut transport = UreqTransport::with_options(base_url, &self.http_options)?;
+        transport.set_api_version(self.api_version);
+        #[cfg(all(feature = "async", feature = "reqwest"))]
+        if let Some(http_client) = self.http_client {
+            transport.set_client(http_client);
+        }
+        #[cfg(all(feature = "blocking", feature = "reqwest"))]
+        if let Some(blocking_http_client) = self.blocking_http_client {
+            transport.set_blocking_client(blocking_http_client);
+        }
+
+        let mut client = SocketLabsClient::with_credentials(self.credentials);
+        client.transport = Box::new(transport);
+        client.validate = self.validate;
+        Ok(client)
+    }
+}
+
+impl SocketLabsClient {
+    /// Creates a builder for a client with the given credentials.
+    pub fn builder<T: Into<String>>(server_id: u16, api_key: T) -> ClientBuilder {
+        ClientBuilder {
+            credentials: Credentials::new(server_id, api_key),
+            base_url: None,
+            api_version: ApiVersion::default(),
+            http_options: HttpOptions::default(),
+            validate: false,
+            #[cfg(all(feature = "async", feature = "reqwest"))]
+            http_client: None,
+            #[cfg(all(feature = "blocking", feature = "reqwest"))]
+            blocking_http_client: None,
+        }
+    }
+
+    /// Creates a new client with the given credentials.
+    pub fn new<T: Into<String>>(server_id: u16, api_key: T) -> SocketLabsClient {
+        SocketLabsClient::with_credentials(Credentials::new(server_id, api_key))
+    }
+
+    /// Creates a new client with the credentials from the
+    /// `credentials::SERVER_ID_VAR` and `credentials::API_KEY_VAR`
+    /// environment variables, failing if either is missing or invalid.
+    pub fn from_env() -> Result<SocketLabsClient> {
+        Ok(SocketLabsClient::with_credentials(
+            EnvCredentials.credentials()?,
+        ))
+    }
+
+    /// Creates a new client sending with `credentials`.
+    pub(crate) fn with_credentials(credentials: Credentials) -> SocketLabsClient {
+        SocketLabsClient {
+            credentials: ArcSwap::from_pointee(credentials),
+            credentials_provider: None,
+            #[cfg(feature = "reqwest")]
+            transport: Box::new(ReqwestTransport::new()),
+            #[cfg(not(feature = "reqwest"))]
+            transport: Box::new(UreqTransport::new()),
+            mode: ClientMode::Live,
+            validate: false,
+            max_in_flight: 1,
+            options: SendOptions::default(),
+            #[cfg(any(feature = "async", feature = "blocking"))]
+            circuit_breaker: None,
+            #[cfg(any(feature = "async", feature = "blocking"))]
+            rate_limiter: None,
+            dead_letter_sink: None,
+        }
+    }
+
+    /// Replaces the credentials of the client, such as when rotating
+    /// its api key, while it may be sending. Sends already under way
+    /// finish with the credentials they started with, including their
+    /// retries, and the following ones use the new credentials.
+    pub fn set_credentials<T: Into<String>>(&self, server_id: u16, api_key: T) {
+        self.credentials
+            .store(Arc::new(Credentials::new(server_id, api_key)));
+    }
+
+    /// Sets a provider looking up the credentials every time messages
+    /// are sent, instead of the ones the client was created with.
+    /// See `credentials::CredentialsProvider`.
+    pub fn set_credentials_provider<P: CredentialsProvider + 'static>(&mut self, provider: P) {
+        self.credentials_provider = Some(Box::new(provider))
+    }
+
+    /// The id of the server the client currently sends to,
+    /// unless a credentials provider is set.
+    pub fn server_id(&self) -> u16 {
+        self.credentials.load().server_id()
+    }
+
+    /// Sets how many injection requests `send_async` may have in flight
+    /// at once, when sending more than `request::MAX_MESSAGES_PER_REQUEST`
+    /// messages. Defaults to 1, sending one request after the other,
+    /// and 0 is treated as 1.
+    pub fn set_max_in_flight(&mut self, max_in_flight: usize) {
+        self.max_in_flight = max_in_flight.max(1)
+    }
+
+    /// Sets the transport injection requests are sent
+    /// with, instead of `transport::ReqwestTransport`.
+    pub fn set_transport<T: Transport + 'static>(&mut self, transport: T) {
+        self.transport = Box::new(transport)
+    }
+
+    /// Sets whether messages are actually sent. Defaults to `ClientMode::Live`.
+    pub fn set_mode(&mut self, mode: ClientMode) {
+        self.mode = mode
+    }
+
+    /// Sets whether every message is checked with `Message::validate`
+    /// before sending, failing locally instead of at SocketLabs.
+    /// Messages are always validated in `ClientMode::DryRun`.
+    pub fn set_validate(&mut self, validate: bool) {
+        self.validate = validate
+    }
+
+    /// Sets how injection requests failing with
+    /// a transient error are retried.
+    pub fn set_send_options(&mut self, options: SendOptions) {
+        self.options = options
+    }
+
+    /// Sets a circuit breaker around every injection request,
+    /// retries included. See `breaker::CircuitBreaker`.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    pub fn set_circuit_breaker(&mut self, circuit_breaker: CircuitBreaker) {
+        self.circuit_breaker = Some(circuit_breaker)
+    }
+
+    /// Sets a rate limiter, throttling every injection
+    /// request, retries included. See `limiter::RateLimiter`.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    pub fn set_rate_limiter(&mut self, rate_limiter: RateLimiter) {
+        self.rate_limiter = Some(rate_limiter)
+    }
+
+    /// Sets the sink receiving every message which could
+    /// not be sent. See `dead_letter::DeadLetterSink`.
+    pub fn set_dead_letter_sink<S: DeadLetterSink + 'static>(&mut self, sink: S) {
+        self.dead_letter_sink = Some(Box::new(sink))
+    }
+
+    /// Sends the given messages, in as many
+    /// injection requests as needed.
+    ///
+    /// When only some of those requests fail, the others are still
+    /// sent and the send fails with `ErrorKind::PartiallySent`, which
+    /// holds their combined response along with the failed messages.
+    ///
+    /// This blocks the current thread and must not be
+    /// called from within an async runtime, use `send_async` there.
+    #[cfg(feature = "blocking")]
+    pub fn send(&self, messages: &[Message]) -> Result<Response<'static>> {
+        let results = self.send_requests(messages)?;
+        for (indices, result) in &results {
+            self.dead_letter(&messages[indices.clone()], result);
+        }
+
+        Response::combine(results)
+    }
+
+    /// Sends the injection requests of the given messages, returning
+    /// the result of each along with the range of its messages. The
+    /// messages which could not be sent are not dead lettered.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn send_requests(
+        &self,
+        messages: &[Message],
+    ) -> Result<Vec<(Range<usize>, Result<Response<'static>>)>> {
+        let mut results = Vec::new();
+        for (offset, body) in self.bodies(messages)? {
+            let body = Bytes::from(body);
+            let count = chunk_len(messages.len(), offset);
+            let result = retry_blocking(&self.options, || {
+                self.guard(count, || post_blocking(self.transport(), &body))
+            });
+            results.push((offset..offset + count, result));
+        }
+
+        Ok(results)
+    }
+
+    /// Sends the given messages, in as many injection requests
+    /// as needed, without blocking the current thread.
+    /// See `SocketLabsClient::send`.
+    #[cfg(feature = "async")]
+    pub async fn send_async(&self, messages: &[Message<'_>]) -> Result<Response<'static>> {
+        let results = self.send_requests_async(messages).await?;
+        for (indices, result) in &results {
+            self.dead_letter(&messages[indices.clone()], result);
+        }
+
+        Response::combine(results)
+    }
+
+    /// Sends the injection requests of the given messages without
+    /// blocking the current thread. See `SocketLabsClient::send_requests`.
+    #[cfg(feature = "async")]
+    pub(crate) async fn send_requests_async(
+        &self,
+        messages: &[Message<'_>],
+    ) -> Result<Vec<(Range<usize>, Result<Response<'static>>)>> {
+        let count = messages.len();
+        let mut results: Vec<_> = stream::iter(self.bodies(messages)?)
+            .map(|(offset, body)| {
+                let indices = offset..offset + chunk_len(count, offset);
+                self.post(body, indices.len())
+                    .map(move |result| (indices, result))
+            })
+            .buffer_unordered(self.max_in_flight)
+            .collect()
+            .await;
+        results.sort_by_key(|(indices, _)| indices.start);
+
+        Ok(results)
+    }
+
+    /// Sends again the messages of `response` which failed with
+    /// a retryable error code, rebuilt by only keeping the recipients
+    /// worth sending to again. Message results of the returned
+    /// response point to the original positions in `messages`.
+    ///
+    /// Every message and address error code SocketLabs documents is
+    /// permanent, so only the messages and recipients which failed with
+    /// `UnknownErrorCode` are sent again, see `MessageResultErrorCode::is_retryable`
+    /// and `AddressResultErrorCode::is_retryable`. In particular, the
+    /// recipients of `Warning` messages, which failed with `InvalidAddress`,
+    /// are never sent to again. When nothing is worth resending, no request
+    /// is sent and the returned response holds no message results.
+    ///
+    /// Requests failing as a whole aren't sent again, those are
+    /// retried as configured with `set_send_options`.
+    ///
+    /// This blocks the current thread and must not be called
+    /// from within an async runtime, use `resend_failed_async` there.
+    #[cfg(feature = "blocking")]
+    pub fn resend_failed(
+        &self,
+        messages: &[Message],
+        response: &Response,
+    ) -> Result<Response<'static>> {
+        let (indices, batch) = response.failed_batch(messages);
+        if batch.is_empty() {
+            return Ok(Response::aggregate(Vec::new()));
+        }
+
+        Ok(self.send(&batch)?.reindex(&indices))
+    }
+
+    /// Sends again the messages of `response` which failed
+    /// with a retryable error code, without blocking the
+    /// current thread. Only `UnknownErrorCode` messages and
+    /// recipients are resent, see `SocketLabsClient::resend_failed`.
+    #[cfg(feature = "async")]
+    pub async fn resend_failed_async(
+        &self,
+        messages: &[Message<'_>],
+        response: &Response<'_>,
+    ) -> Result<Response<'static>> {
+        let (indices, batch) = response.failed_batch(messages);
+        if batch.is_empty() {
+            return Ok(Response::aggregate(Vec::new()));
+        }
+
+        Ok(self.send_async(&batch).await?.reindex(&indices))
+    }
+
+    /// Sends the given messages without blocking the current thread,
+    /// yielding the outcome of every message, along with its index in
+    /// `messages`, as soon as the injection request it is part of
+    /// completes. See `SocketLabsClient::set_max_in_flight`.
+    ///
+    /// Messages of an injection request which fails as a whole, such as
+    /// with a network error, all yield that same error.
+    #[cfg(feature = "async")]
+    pub fn send_all<'s>(
+        &'s self,
+        messages: &'s [Message<'_>],
+    ) -> Result<impl Stream<Item = (usize, Result<MessageOutcome>)> + 's> {
+        let count = messages.len();
+        let bodies = self.bodies(messages)?;
+
+        Ok(stream::iter(bodies)
+            .map(move |(offset, body)| {
+                let indices = offset..offset + chunk_len(count, offset);
+                self.post(body, indices.len()).map(move |response| {
+                    self.dead_letter(&messages[indices.clone()], &response);
+                    (indices, response)
+                })
+            })
+            .buffer_unordered(self.max_in_flight)
+            .flat_map(|(indices, response)| {
+                let outcomes = match response {
+                    Ok(response) => response.outcomes(indices),
+                    Err(error) => indices
+                        .map(|index| (index, Err(Error::from(error.kind().clone()))))
+                        .collect(),
+                };
+                stream::iter(outcomes)
+            }))
+    }
+
+    /// Sends a single injection request, of `count` messages,
+    /// without blocking the current thread.
+    #[cfg(feature = "async")]
+    async fn post(&self, body: Vec<u8>, count: usize) -> Result<Response<'static>> {
+        let body = Bytes::from(body);
+        retry(&self.options, || self.guarded_post(&body, count)).await
+    }
+
+    /// Hands the messages of an injection request, `chunk`,
+    /// which could not be sent to the dead letter sink.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    pub(crate) fn dead_letter(&self, chunk: &[Message], result: &Result<Response<'static>>) {
+        let sink = match self.dead_letter_sink {
+            Some(ref sink) => sink,
+            None => return,
+        };
+
+        let response = match result {
+            Ok(response) => response,
+            Err(error) => {
+                chunk
+                    .iter()
+                    .for_each(|message| sink.dead_letter(message, error));
+                return;
+            }
+        };
+        match response.error_code {
+            PostMessageErrorCode::Success | PostMessageErrorCode::Warning => {}
+            error_code => {
+                let error = ErrorKind::InjectionFailed(error_code).into();
+                chunk
+                    .iter()
+                    .for_each(|message| sink.dead_letter(message, &error));
+                return;
+            }
+        }
+        for result in response.message_results.iter().flatten() {
+            if result.error_code == MessageResultErrorCode::Warning {
+                continue;
+            }
+            if let Some(message) = chunk.get(result.index) {
+                sink.dead_letter(
+                    message,
+                    &ErrorKind::MessageRejected(result.error_code).into(),
+                );
+            }
+        }
+    }
+
+    /// Makes a single attempt at an injection request through
+    /// the circuit breaker and rate limiter, if any.
+    #[cfg(feature = "async")]
+    async fn guarded_post(&self, body: &Bytes, count: usize) -> Result<Response<'static>> {
+        if self.mode == ClientMode::DryRun {
+            return post(self.transport(), body).await;
+        }
+        if let Some(ref circuit_breaker) = self.circuit_breaker {
+            circuit_breaker.check()?;
+        }
+        if let Some(ref rate_limiter) = self.rate_limiter {
+            sleep(rate_limiter.reserve(count)).await;
+        }
+        let result = post(self.transport(), body).await;
+        if let Some(ref circuit_breaker) = self.circuit_breaker {
+            circuit_breaker.record(&result);
+        }
+        result
+    }
+
+    /// Makes a single blocking attempt at an injection request
+    /// of `count` messages through the circuit breaker and
+    /// rate limiter, if any.
+    #[cfg(feature = "blocking")]
+    fn guard<F>(&self, count: usize, attempt: F) -> Result<Response<'static>>
+    where
+        F: FnOnce() -> Result<Response<'static>>,
+    {
+        if self.mode == ClientMode::DryRun {
+            return attempt();
+        }
+        if let Some(ref circuit_breaker) = self.circuit_breaker {
+            circuit_breaker.check()?;
+        }
+        if let Some(ref rate_limiter) = self.rate_limiter {
+            thread::sleep(rate_limiter.reserve(count));
+        }
+        let result = attempt();
+        if let Some(ref circuit_breaker) = self.circuit_breaker {
+            circuit_breaker.record(&result);
+        }
+        result
+    }
+
+    /// The transport injection requests are sent with in the current mode.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn transport(&self) -> &dyn Transport {
+        match self.mode {
+            ClientMode::Live => &*self.transport,
+            ClientMode::DryRun => &DryRun,
+        }
+    }
+
+    /// Serializes the messages along with the credentials,
+    /// in as many injection requests as needed. Messages are
+    /// validated first in dry runs or when asked to.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn bodies(&self, messages: &[Message]) -> Result<Vec<(usize, Vec<u8>)>> {
+        if self.validate || self.mode == ClientMode::DryRun {
+            validate_messages(messages)?;
+        }
+
+        let credentials = match self.credentials_provider {
+            Some(ref provider) => Arc::new(provider.credentials()?),
+            None => self.credentials.load_full(),
+        };
+        log_debug!(
+            "Sending {} message(s) to {} (server_id: {}, api_key: <redacted>)",
+            messages.len(),
+            self.transport()
+                .endpoint()
+                .as_deref()
+                .unwrap_or("an unknown endpoint"),
+            credentials.server_id()
+        );
+
+        injection_bodies(
+            credentials.server_id(),
+            credentials.api_key().expose_secret(),
+            messages,
+        )
+    }
+}
+
+/// How many of `count` messages are part of
+/// the injection request starting at `offset`.
+#[cfg(any(feature = "async", feature = "blocking"))]
+fn chunk_len(count: usize, offset: usize) -> usize {
+    count.min(offset + MAX_MESSAGES_PER_REQUEST) - offset
+}
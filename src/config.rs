@@ -0,0 +1,334 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Client settings which can be loaded from a configuration file
+//! and environment variables, so they can be tuned without changing code.
+
+use std::env;
+#[cfg(feature = "toml")]
+use std::fs;
+#[cfg(feature = "toml")]
+use std::io;
+#[cfg(feature = "toml")]
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::client::SocketLabsClient;
+use crate::credentials::{API_KEY_VAR, SERVER_ID_VAR};
+use crate::error::{ErrorKind, Result};
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::limiter::RateLimiter;
+use crate::retry::SendOptions;
+use crate::secret::SecretString;
+use crate::transport::{BASE_URL_VAR, DEFAULT_BASE_URL};
+
+/// The settings of a `SocketLabsClient`. Every one of them is
+/// optional, except for the credentials when building the client.
+///
+/// With the `toml` feature, they can be read from a TOML file,
+/// where durations are given in seconds:
+///
+/// ```toml
+/// server_id = 1234
+/// api_key = "api-key"
+/// base_url = "https://inject.socketlabs.com"
+/// connect_timeout = 5
+/// timeout = 30
+///
+/// [retry]
+/// max_retries = 3
+/// backoff = 0.5
+/// max_backoff = 60
+///
+/// [rate_limit]
+/// messages_per_second = 100
+/// requests_per_second = 10
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientConfig {
+    /// The id of the SocketLabs server.
+    pub server_id: Option<u16>,
+    /// The api key of the SocketLabs server.
+    pub api_key: Option<SecretString>,
+    /// The base URL of the Injection API.
+    /// See `ClientBuilder::base_url`.
+    pub base_url: Option<String>,
+    /// How long connecting to SocketLabs may take.
+    #[serde(deserialize_with = "seconds")]
+    pub connect_timeout: Option<Duration>,
+    /// How long every injection request may take as a whole.
+    #[serde(deserialize_with = "seconds")]
+    pub timeout: Option<Duration>,
+    /// How failed injection requests are retried.
+    pub retry: RetryConfig,
+    /// How injection requests are throttled.
+    pub rate_limit: RateLimitConfig,
+}
+
+/// The retry policy of a client, see `retry::SendOptions`.
+/// Unset settings keep their default.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// How many times a request is retried.
+    pub max_retries: Option<u32>,
+    /// The delay before the first retry.
+    #[serde(deserialize_with = "seconds")]
+    pub backoff: Option<Duration>,
+    /// The longest delay between two attempts.
+    #[serde(deserialize_with = "seconds")]
+    pub max_backoff: Option<Duration>,
+}
+
+/// The rate limits of a client, see `limiter::RateLimiter`.
+/// Nothing is throttled unless either is set.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// How many messages may be sent per second.
+    pub messages_per_second: Option<u32>,
+    /// How many injection requests may be sent per second.
+    pub requests_per_second: Option<u32>,
+}
+
+/// Resolves the settings of a client from several layers, each setting
+/// taken from the first layer which sets it, in order of precedence:
+///
+/// 1. the settings given programmatically with `ConfigBuilder::settings`,
+/// 2. the environment variables, see `ClientConfig::from_env`,
+/// 3. the configuration file given with `ConfigBuilder::file`, if any,
+/// 4. the defaults.
+///
+/// The same binary can then run in several environments, only
+/// overriding the file, or the defaults, through the environment.
+///
+/// ```no_run
+/// use socketlabs::config::ClientConfig;
+///
+/// let client = ClientConfig::builder().build().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct ConfigBuilder {
+    settings: ClientConfig,
+    env: bool,
+    #[cfg(feature = "toml")]
+    path: Option<PathBuf>,
+}
+
+impl ConfigBuilder {
+    /// Sets the settings overriding every other layer.
+    pub fn settings(mut self, settings: ClientConfig) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Sets the configuration file, which is skipped if it does not exist.
+    #[cfg(feature = "toml")]
+    pub fn file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Ignores the environment variables.
+    pub fn no_env(mut self) -> Self {
+        self.env = false;
+        self
+    }
+
+    /// Merges the layers into the resolved settings.
+    pub fn resolve(self) -> Result<ClientConfig> {
+        let mut config = self.settings;
+        if self.env {
+            config = config.merge(ClientConfig::from_env()?);
+        }
+        #[cfg(feature = "toml")]
+        if let Some(path) = self.path {
+            match fs::read_to_string(&path) {
+                Ok(toml) => config = config.merge(ClientConfig::from_toml(&toml)?),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Builds a client with the resolved settings.
+    pub fn build(self) -> Result<SocketLabsClient> {
+        self.resolve()?.build()
+    }
+}
+
+impl ClientConfig {
+    /// Creates a builder resolving the settings from several layers.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder {
+            settings: ClientConfig::default(),
+            env: true,
+            #[cfg(feature = "toml")]
+            path: None,
+        }
+    }
+
+    /// Reads the settings from the environment variables
+    /// `SOCKETLABS_SERVER_ID`, `SOCKETLABS_API_KEY`,
+    /// `SOCKETLABS_BASE_URL`, `SOCKETLABS_CONNECT_TIMEOUT`,
+    /// `SOCKETLABS_TIMEOUT`, `SOCKETLABS_MAX_RETRIES`,
+    /// `SOCKETLABS_BACKOFF`, `SOCKETLABS_MAX_BACKOFF`,
+    /// `SOCKETLABS_MESSAGES_PER_SECOND` and
+    /// `SOCKETLABS_REQUESTS_PER_SECOND`, with durations in seconds.
+    /// Unset variables leave their setting unset.
+    pub fn from_env() -> Result<ClientConfig> {
+        Ok(ClientConfig {
+            server_id: env_var(SERVER_ID_VAR)?,
+            api_key: env::var(API_KEY_VAR).ok().map(SecretString::new),
+            base_url: env::var(BASE_URL_VAR).ok(),
+            connect_timeout: env_seconds("SOCKETLABS_CONNECT_TIMEOUT")?,
+            timeout: env_seconds("SOCKETLABS_TIMEOUT")?,
+            retry: RetryConfig {
+                max_retries: env_var("SOCKETLABS_MAX_RETRIES")?,
+                backoff: env_seconds("SOCKETLABS_BACKOFF")?,
+                max_backoff: env_seconds("SOCKETLABS_MAX_BACKOFF")?,
+            },
+            rate_limit: RateLimitConfig {
+                messages_per_second: env_var("SOCKETLABS_MESSAGES_PER_SECOND")?,
+                requests_per_second: env_var("SOCKETLABS_REQUESTS_PER_SECOND")?,
+            },
+        })
+    }
+
+    /// Fills the settings unset here with the ones of `fallback`.
+    pub fn merge(self, fallback: ClientConfig) -> ClientConfig {
+        ClientConfig {
+            server_id: self.server_id.or(fallback.server_id),
+            api_key: self.api_key.or(fallback.api_key),
+            base_url: self.base_url.or(fallback.base_url),
+            connect_timeout: self.connect_timeout.or(fallback.connect_timeout),
+            timeout: self.timeout.or(fallback.timeout),
+            retry: RetryConfig {
+                max_retries: self.retry.max_retries.or(fallback.retry.max_retries),
+                backoff: self.retry.backoff.or(fallback.retry.backoff),
+                max_backoff: self.retry.max_backoff.or(fallback.retry.max_backoff),
+            },
+            rate_limit: RateLimitConfig {
+                messages_per_second: self
+                    .rate_limit
+                    .messages_per_second
+                    .or(fallback.rate_limit.messages_per_second),
+                requests_per_second: self
+                    .rate_limit
+                    .requests_per_second
+                    .or(fallback.rate_limit.requests_per_second),
+            },
+        }
+    }
+
+    /// Reads the settings from a TOML file.
+    #[cfg(feature = "toml")]
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<ClientConfig> {
+        ClientConfig::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Reads the settings from a TOML document.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<ClientConfig> {
+        toml::from_str(toml).map_err(|error| ErrorKind::ConfigError(error.to_string()).into())
+    }
+
+    /// Builds a client with these settings, failing
+    /// if either of the credentials is missing.
+    ///
+    /// An unset `base_url` means `DEFAULT_BASE_URL`, the environment is
+    /// only read through `ClientConfig::from_env` or `ConfigBuilder`.
+    pub fn build(self) -> Result<SocketLabsClient> {
+        let server_id = self
+            .server_id
+            .ok_or_else(|| ErrorKind::ConfigError("server_id is missing".to_string()))?;
+        let api_key = self
+            .api_key
+            .ok_or_else(|| ErrorKind::ConfigError("api_key is missing".to_string()))?;
+
+        let base_url = self
+            .base_url
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+        let mut builder =
+            SocketLabsClient::builder(server_id, api_key.expose_secret()).base_url(base_url);
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let mut client = builder.build()?;
+
+        let mut options = SendOptions::default();
+        if let Some(max_retries) = self.retry.max_retries {
+            options.max_retries = max_retries;
+        }
+        if let Some(backoff) = self.retry.backoff {
+            options.backoff = backoff;
+        }
+        if let Some(max_backoff) = self.retry.max_backoff {
+            options.max_backoff = max_backoff;
+        }
+        client.set_send_options(options);
+
+        #[cfg(any(feature = "async", feature = "blocking"))]
+        let RateLimitConfig {
+            messages_per_second,
+            requests_per_second,
+        } = self.rate_limit;
+        #[cfg(any(feature = "async", feature = "blocking"))]
+        if messages_per_second.is_some() || requests_per_second.is_some() {
+            let mut rate_limiter = RateLimiter::new();
+            if let Some(rate) = messages_per_second {
+                rate_limiter.set_messages_per_second(rate);
+            }
+            if let Some(rate) = requests_per_second {
+                rate_limiter.set_requests_per_second(rate);
+            }
+            client.set_rate_limiter(rate_limiter);
+        }
+
+        Ok(client)
+    }
+}
+
+/// Parses an environment variable, if set.
+fn env_var<T: FromStr>(name: &str) -> Result<Option<T>> {
+    match env::var(name) {
+        Ok(value) => value.trim().parse().map(Some).map_err(|_| {
+            ErrorKind::ConfigError(format!("{} is not a valid value: {}", name, value)).into()
+        }),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Parses an environment variable holding a duration in seconds, if set.
+fn env_seconds(name: &str) -> Result<Option<Duration>> {
+    env_var(name)?
+        .map(|seconds| {
+            Duration::try_from_secs_f64(seconds).map_err(|error| {
+                ErrorKind::ConfigError(format!("{} is not a valid duration: {}", name, error))
+                    .into()
+            })
+        })
+        .transpose()
+}
+
+/// Deserializes a duration given in seconds.
+fn seconds<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Duration>, D::Error> {
+    match Option::<f64>::deserialize(deserializer)? {
+        Some(seconds) => Duration::try_from_secs_f64(seconds)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,125 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Injection API credentials, and providers looking
+//! them up every time messages are sent.
+
+use std::path::PathBuf;
+use std::{env, fmt, fs};
+
+use crate::error::{ErrorKind, Result};
+use crate::secret::SecretString;
+
+/// The environment variable holding the id of the SocketLabs server.
+pub const SERVER_ID_VAR: &str = "SOCKETLABS_SERVER_ID";
+
+/// The environment variable holding the api key of the SocketLabs server.
+pub const API_KEY_VAR: &str = "SOCKETLABS_API_KEY";
+
+/// The id of a SocketLabs server along with its api key.
+#[derive(Clone, Debug)]
+pub struct Credentials {
+    server_id: u16,
+    api_key: SecretString,
+}
+
+impl Credentials {
+    /// Creates new credentials.
+    pub fn new<T: Into<String>>(server_id: u16, api_key: T) -> Credentials {
+        Credentials {
+            server_id,
+            api_key: SecretString::new(api_key.into()),
+        }
+    }
+
+    /// The id of the SocketLabs server.
+    pub fn server_id(&self) -> u16 {
+        self.server_id
+    }
+
+    /// The api key of the SocketLabs server.
+    pub fn api_key(&self) -> &SecretString {
+        &self.api_key
+    }
+}
+
+/// Looks up the credentials a client sends with, every time it
+/// sends, such as from a secret manager rotating the api key.
+///
+/// Closures returning credentials implement this trait.
+pub trait CredentialsProvider: Send + Sync {
+    /// The credentials to send the next injection requests with.
+    fn credentials(&self) -> Result<Credentials>;
+}
+
+impl<F> CredentialsProvider for F
+where
+    F: Fn() -> Result<Credentials> + Send + Sync,
+{
+    fn credentials(&self) -> Result<Credentials> {
+        self()
+    }
+}
+
+impl fmt::Debug for dyn CredentialsProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("CredentialsProvider")
+    }
+}
+
+/// Reads the credentials from the `SERVER_ID_VAR`
+/// and `API_KEY_VAR` environment variables.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EnvCredentials;
+
+impl CredentialsProvider for EnvCredentials {
+    fn credentials(&self) -> Result<Credentials> {
+        let server_id = env::var(SERVER_ID_VAR)
+            .map_err(|_| ErrorKind::CredentialsError(format!("{} is not set", SERVER_ID_VAR)))?;
+        let server_id = server_id.trim().parse().map_err(|_| {
+            ErrorKind::CredentialsError(format!(
+                "{} is not a valid server id: {}",
+                SERVER_ID_VAR, server_id
+            ))
+        })?;
+        let api_key = env::var(API_KEY_VAR)
+            .map_err(|_| ErrorKind::CredentialsError(format!("{} is not set", API_KEY_VAR)))?;
+
+        Ok(Credentials::new(server_id, api_key))
+    }
+}
+
+/// Reads the api key of a server from a file, such as a
+/// secret mounted by an orchestrator, every time it is needed.
+/// Surrounding whitespace is ignored.
+#[derive(Clone, Debug)]
+pub struct FileCredentials {
+    server_id: u16,
+    path: PathBuf,
+}
+
+impl FileCredentials {
+    /// Creates a provider for the server `server_id`,
+    /// whose api key is stored at `path`.
+    pub fn new<P: Into<PathBuf>>(server_id: u16, path: P) -> FileCredentials {
+        FileCredentials {
+            server_id,
+            path: path.into(),
+        }
+    }
+}
+
+impl CredentialsProvider for FileCredentials {
+    fn credentials(&self) -> Result<Credentials> {
+        let api_key = fs::read_to_string(&self.path)?;
+        let api_key = api_key.trim();
+        if api_key.is_empty() {
+            return Err(
+                ErrorKind::CredentialsError(format!("{} is empty", self.path.display())).into(),
+            );
+        }
+
+        Ok(Credentials::new(self.server_id, api_key))
+    }
+}
@@ -0,0 +1,38 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A hook receiving the messages which could not be sent.
+
+use std::fmt;
+
+use crate::error::Error;
+use crate::message::Message;
+
+/// Receives every message the client gave up on, along with the
+/// final error, so it can be persisted or alerted on.
+///
+/// A message is given up on when the injection request it is part
+/// of still fails after its retries, or when SocketLabs rejects it.
+/// Messages which only have some bad recipients are not.
+///
+/// Closures taking a message and an error implement this trait.
+pub trait DeadLetterSink: Send + Sync {
+    /// Receives a message which could not be sent.
+    fn dead_letter(&self, message: &Message<'_>, error: &Error);
+}
+
+impl<F> DeadLetterSink for F
+where
+    F: Fn(&Message<'_>, &Error) + Send + Sync,
+{
+    fn dead_letter(&self, message: &Message<'_>, error: &Error) {
+        self(message, error)
+    }
+}
+
+impl fmt::Debug for dyn DeadLetterSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("DeadLetterSink")
+    }
+}
@@ -0,0 +1,355 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Conversion between messages for the Injection API
+//! and raw RFC 822 documents, such as `.eml` files.
+
+use std::borrow::Cow;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+#[cfg(feature = "eml")]
+use mail_parser::{Address, MessageParser, MimeHeaders, PartType};
+use uuid::Uuid;
+
+use crate::encoding::encode_word;
+#[cfg(feature = "eml")]
+use crate::error::{ErrorKind, Result};
+use crate::message::{Attachment, Email, Message};
+
+/// The maximum length of a line of base64 encoded content.
+const MAX_LINE_LENGTH: usize = 76;
+
+/// The length header lines are folded to, whenever they can be.
+const FOLDED_LINE_LENGTH: usize = 78;
+
+/// The length no line may exceed, as set by RFC 5322.
+const LINE_LENGTH_LIMIT: usize = 998;
+
+/// Headers which are either mapped to a field of the
+/// message or generated by SocketLabs when sending it.
+#[cfg(feature = "eml")]
+const MAPPED_HEADERS: &[&str] = &[
+    "From",
+    "To",
+    "Cc",
+    "Bcc",
+    "Reply-To",
+    "Subject",
+    "Date",
+    "Message-ID",
+    "MIME-Version",
+    "Received",
+    "Return-Path",
+];
+
+#[cfg(feature = "eml")]
+impl Message<'static> {
+    /// Builds a message from a raw RFC 822 document.
+    ///
+    /// The sender, recipients, subject, first text and html
+    /// bodies and attachments are mapped to their fields in the
+    /// Message struct, any other header which isn't generated by
+    /// SocketLabs is kept as a custom header. Bodies and headers
+    /// are decoded, so the charset of the document is not kept.
+    pub fn from_eml<B: AsRef<[u8]>>(eml: B) -> Result<Message<'static>> {
+        let eml = MessageParser::default()
+            .parse(eml.as_ref())
+            .ok_or_else(|| {
+                ErrorKind::MessageParsingError("not a valid RFC 822 document".to_string())
+            })?;
+
+        let (from, name) = eml
+            .from()
+            .and_then(Address::first)
+            .map(|from| {
+                (
+                    from.address().unwrap_or_default().to_string(),
+                    from.name().map(str::to_string),
+                )
+            })
+            .unwrap_or_default();
+        let mut message = Message::new(from, name);
+
+        for to in eml.to().into_iter().flat_map(Address::iter) {
+            if let Some(address) = to.address() {
+                message.add_to(address.to_string(), to.name().map(str::to_string));
+            }
+        }
+        for cc in eml.cc().into_iter().flat_map(Address::iter) {
+            if let Some(address) = cc.address() {
+                message.add_cc(address.to_string(), cc.name().map(str::to_string));
+            }
+        }
+        for bcc in eml.bcc().into_iter().flat_map(Address::iter) {
+            if let Some(address) = bcc.address() {
+                message.add_bcc(address.to_string(), bcc.name().map(str::to_string));
+            }
+        }
+        if let Some(reply_to) = eml.reply_to().and_then(Address::first) {
+            if let Some(address) = reply_to.address() {
+                message.set_reply_to(address.to_string(), reply_to.name().map(str::to_string));
+            }
+        }
+        if let Some(subject) = eml.subject() {
+            message.set_subject(subject.to_string());
+        }
+
+        // Documents with a single body have it listed as both
+        // text and html, only its actual type is kept here.
+        if let Some(PartType::Text(text)) = eml.text_part(0).map(|part| &part.body) {
+            message.set_text(text.to_string());
+        }
+        if let Some(PartType::Html(html)) = eml.html_part(0).map(|part| &part.body) {
+            message.set_html(html.to_string());
+        }
+
+        for part in eml.attachments() {
+            let content_type = part
+                .content_type()
+                .map(|content_type| match content_type.subtype() {
+                    Some(subtype) => format!("{}/{}", content_type.ctype(), subtype),
+                    None => content_type.ctype().to_string(),
+                })
+                .unwrap_or_else(|| "application/octet-stream".to_string());
+            let name = part.attachment_name().unwrap_or("attachment").to_string();
+            let mut attachment = Attachment::new(name, content_type, part.contents());
+            if let Some(content_id) = part.content_id() {
+                attachment.set_content_id(content_id.to_string());
+            }
+            message.attach(attachment);
+        }
+
+        for (name, value) in eml.headers_raw() {
+            let mapped = MAPPED_HEADERS
+                .iter()
+                .any(|mapped| mapped.eq_ignore_ascii_case(name));
+            if mapped || name.to_ascii_lowercase().starts_with("content-") {
+                continue;
+            }
+            // Folded values are unfolded back into a single line.
+            let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+            message.add_header(name.to_string(), value);
+        }
+
+        Ok(message)
+    }
+}
+
+impl<'a> Message<'a> {
+    /// Renders the message as a standalone MIME document,
+    /// which can be saved as an `.eml` file.
+    ///
+    /// Bodies are always written as UTF-8, regardless of the
+    /// charset of the message. Merge fields are left as they are
+    /// and messages relying on an api template have no body, since
+    /// both are only resolved by SocketLabs when sending.
+    ///
+    /// Line breaks are removed from header names and values, so that
+    /// they can't inject other headers, and long headers are folded.
+    pub fn to_eml(&self) -> String {
+        let mut eml = String::new();
+
+        push_header(&mut eml, "From", &mailbox(self.from()));
+        for (name, emails) in &[("To", self.to()), ("Cc", self.cc()), ("Bcc", self.bcc())] {
+            if !emails.is_empty() {
+                let mailboxes = emails.iter().map(mailbox).collect::<Vec<_>>();
+                push_header(&mut eml, name, &mailboxes.join(", "));
+            }
+        }
+        if let Some(reply_to) = self.reply_to() {
+            push_header(&mut eml, "Reply-To", &mailbox(reply_to));
+        }
+        push_header(&mut eml, "Subject", &encode_word(self.subject()));
+        if let Some(message_id) = self.message_id() {
+            push_header(&mut eml, "X-xsMessageId", &encode_word(message_id));
+        }
+        if let Some(mailing_id) = self.mailing_id() {
+            push_header(&mut eml, "X-xsMailingId", &encode_word(mailing_id));
+        }
+        for header in self.custom_headers() {
+            push_header(&mut eml, header.name(), &encode_word(header.value()));
+        }
+        push_header(&mut eml, "MIME-Version", "1.0");
+
+        let mut bodies = Vec::new();
+        if !self.text_body().is_empty() {
+            bodies.push(text_part("text/plain", self.text_body()));
+        }
+        if let Some(amp) = self.amp_body() {
+            bodies.push(text_part("text/x-amp-html", amp));
+        }
+        if let Some(html) = self.html_body() {
+            bodies.push(text_part("text/html", html));
+        }
+        let body = match bodies.len() {
+            0 => text_part("text/plain", ""),
+            1 => bodies.remove(0),
+            _ => multipart("alternative", &bodies),
+        };
+
+        if self.attachments().is_empty() {
+            eml.push_str(&body);
+        } else {
+            let mut parts = vec![body];
+            parts.extend(self.attachments().iter().map(attachment_part));
+            eml.push_str(&multipart("mixed", &parts));
+        }
+
+        eml
+    }
+}
+
+/// Appends a header line to a MIME entity.
+///
+/// Characters which aren't allowed in a header name are dropped,
+/// and line breaks in the value are replaced by spaces. The value
+/// is then folded at spaces, to keep lines under 78 characters where
+/// possible. Words too long for the 998 characters limit are split.
+fn push_header(entity: &mut String, name: &str, value: &str) {
+    let name = name
+        .chars()
+        .filter(|c| c.is_ascii_graphic() && *c != ':')
+        .collect::<String>();
+    entity.push_str(&name);
+    entity.push(':');
+
+    let value = value
+        .split(['\r', '\n'])
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut line_length = name.len() + 1;
+    for (index, mut word) in value.split(' ').enumerate() {
+        if index > 0 && !word.is_empty() && line_length + 1 + word.len() > FOLDED_LINE_LENGTH {
+            entity.push_str("\r\n");
+            line_length = 0;
+        }
+        entity.push(' ');
+        line_length += 1;
+
+        while line_length + word.len() > LINE_LENGTH_LIMIT {
+            let mut end = LINE_LENGTH_LIMIT - line_length;
+            while !word.is_char_boundary(end) {
+                end -= 1;
+            }
+            entity.push_str(&word[..end]);
+            entity.push_str("\r\n ");
+            line_length = 1;
+            word = &word[end..];
+        }
+        entity.push_str(word);
+        line_length += word.len();
+    }
+    entity.push_str("\r\n");
+}
+
+/// Formats an email as an RFC 5322 mailbox.
+fn mailbox(email: &Email) -> String {
+    match email.friendly_name() {
+        Some(name) if !name.is_empty() => format!("{} <{}>", display_name(name), email.address()),
+        _ => email.address().to_string(),
+    }
+}
+
+/// Encodes non-ASCII display names and quotes
+/// the ones containing special characters.
+fn display_name(name: &str) -> Cow<'_, str> {
+    if !name.is_ascii() {
+        encode_word(name)
+    } else if name.contains(|c| "()<>[]:;@\\,.\"".contains(c)) {
+        Cow::Owned(quote(name))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Quotes a header parameter or display name.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Builds a text entity, base64 encoding it unless it
+/// is ASCII and fits the line length limit of RFC 5322.
+fn text_part(content_type: &str, text: &str) -> String {
+    let mut part = String::new();
+    push_header(
+        &mut part,
+        "Content-Type",
+        &format!("{}; charset=utf-8", content_type),
+    );
+
+    if text.is_ascii() && text.lines().all(|line| line.len() <= LINE_LENGTH_LIMIT) {
+        push_header(&mut part, "Content-Transfer-Encoding", "7bit");
+        part.push_str("\r\n");
+        part.push_str(&text.lines().collect::<Vec<_>>().join("\r\n"));
+    } else {
+        push_header(&mut part, "Content-Transfer-Encoding", "base64");
+        part.push_str("\r\n");
+        part.push_str(&wrap(&STANDARD.encode(text)));
+    }
+
+    part
+}
+
+/// Builds the entity of an attachment, which is
+/// inline when it can be referenced by its content id.
+fn attachment_part(attachment: &Attachment) -> String {
+    let name = quote(&encode_word(attachment.name()));
+    let disposition = match attachment.content_id() {
+        Some(_) => "inline",
+        None => "attachment",
+    };
+
+    let mut part = String::new();
+    push_header(
+        &mut part,
+        "Content-Type",
+        &format!("{}; name={}", attachment.content_type(), name),
+    );
+    push_header(
+        &mut part,
+        "Content-Disposition",
+        &format!("{}; filename={}", disposition, name),
+    );
+    push_header(&mut part, "Content-Transfer-Encoding", "base64");
+    if let Some(content_id) = attachment.content_id() {
+        push_header(&mut part, "Content-ID", &format!("<{}>", content_id));
+    }
+    for header in attachment.custom_headers() {
+        push_header(&mut part, header.name(), &encode_word(header.value()));
+    }
+    part.push_str("\r\n");
+    part.push_str(&wrap(&STANDARD.encode(attachment.content())));
+
+    part
+}
+
+/// Builds a multipart entity out of the given entities.
+fn multipart(subtype: &str, parts: &[String]) -> String {
+    let boundary = format!("=_{}", Uuid::new_v4().simple());
+
+    let mut entity = String::new();
+    push_header(
+        &mut entity,
+        "Content-Type",
+        &format!("multipart/{}; boundary=\"{}\"", subtype, boundary),
+    );
+    entity.push_str("\r\n");
+    for part in parts {
+        entity.push_str(&format!("--{}\r\n{}\r\n", boundary, part));
+    }
+    entity.push_str(&format!("--{}--\r\n", boundary));
+
+    entity
+}
+
+/// Splits base64 encoded content in lines.
+fn wrap(base64: &str) -> String {
+    base64
+        .as_bytes()
+        .chunks(MAX_LINE_LENGTH)
+        .map(|line| String::from_utf8_lossy(line))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
@@ -0,0 +1,149 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Encodings used for email headers.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::error::{Error, ErrorKind, Result};
+
+/// The maximum number of bytes encoded in a single encoded-word,
+/// which keeps every word under the 75 characters limit of RFC 2047.
+const MAX_WORD_BYTES: usize = 45;
+
+/// Encodes a header value as RFC 2047 `=?UTF-8?B?...?=` encoded-words
+/// if it contains any non-ASCII character, returns it unchanged otherwise.
+///
+/// Long values are split in several encoded-words separated
+/// by spaces, never splitting a character in two.
+pub fn encode_word(value: &str) -> Cow<'_, str> {
+    if value.is_ascii() {
+        return Cow::Borrowed(value);
+    }
+
+    let mut words = Vec::new();
+    let mut start = 0;
+    while start < value.len() {
+        let mut end = (start + MAX_WORD_BYTES).min(value.len());
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        words.push(format!(
+            "=?UTF-8?B?{}?=",
+            STANDARD.encode(&value[start..end])
+        ));
+        start = end;
+    }
+
+    Cow::Owned(words.join(" "))
+}
+
+/// A character set for a message, named as registered with IANA.
+///
+/// Can be passed anywhere a charset name is expected, such as
+/// `Message::set_charset`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Charset {
+    UsAscii,
+    Utf8,
+    Utf16,
+    Iso8859_1,
+    Iso8859_2,
+    Iso8859_15,
+    Windows1252,
+    ShiftJis,
+    Iso2022Jp,
+    EucJp,
+    EucKr,
+    Gb2312,
+    Big5,
+    Koi8R,
+    /// Any other charset. Prefer building it through
+    /// `Charset::parse`, which checks the name.
+    Other(String),
+}
+
+/// The charsets with their own variant, along with their registered names.
+const KNOWN_CHARSETS: &[(Charset, &str)] = &[
+    (Charset::UsAscii, "US-ASCII"),
+    (Charset::Utf8, "UTF-8"),
+    (Charset::Utf16, "UTF-16"),
+    (Charset::Iso8859_1, "ISO-8859-1"),
+    (Charset::Iso8859_2, "ISO-8859-2"),
+    (Charset::Iso8859_15, "ISO-8859-15"),
+    (Charset::Windows1252, "windows-1252"),
+    (Charset::ShiftJis, "Shift_JIS"),
+    (Charset::Iso2022Jp, "ISO-2022-JP"),
+    (Charset::EucJp, "EUC-JP"),
+    (Charset::EucKr, "EUC-KR"),
+    (Charset::Gb2312, "GB2312"),
+    (Charset::Big5, "Big5"),
+    (Charset::Koi8R, "KOI8-R"),
+];
+
+impl Charset {
+    /// Parses a charset name, case-insensitively.
+    ///
+    /// Names without a variant of their own become `Charset::Other`
+    /// as long as they follow the RFC 2978 `mime-charset` syntax.
+    pub fn parse(name: &str) -> Result<Charset> {
+        let name = name.trim();
+        if let Some((charset, _)) = KNOWN_CHARSETS
+            .iter()
+            .find(|(_, known)| known.eq_ignore_ascii_case(name))
+        {
+            return Ok(charset.clone());
+        }
+
+        let valid = !name.is_empty()
+            && name.len() <= 40
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$%&'+-^_`{}~".contains(c));
+        if !valid {
+            return Err(ErrorKind::InvalidCharset(name.to_string()).into());
+        }
+
+        Ok(Charset::Other(name.to_string()))
+    }
+
+    /// The registered name of the charset.
+    pub fn name(&self) -> &str {
+        match *self {
+            Charset::Other(ref name) => name,
+            ref charset => KNOWN_CHARSETS
+                .iter()
+                .find(|(known, _)| known == charset)
+                .map(|(_, name)| *name)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl FromStr for Charset {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Charset> {
+        Charset::parse(name)
+    }
+}
+
+impl fmt::Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl<'a> From<Charset> for Cow<'a, str> {
+    fn from(charset: Charset) -> Cow<'a, str> {
+        match charset {
+            Charset::Other(name) => Cow::Owned(name),
+            charset => Cow::Owned(charset.name().to_string()),
+        }
+    }
+}
@@ -4,12 +4,16 @@
 
 //! Error definitions.
 
-use std::{fmt, result};
+use std::time::Duration;
+use std::{fmt, io, result};
 
 use failure::{Backtrace, Context, Fail};
+#[cfg(feature = "reqwest")]
 use reqwest::Error as ReqwestError;
 use serde_json::Error as SerdeError;
 
+use crate::response::{MessageResultErrorCode, PartialResponse, PostMessageErrorCode};
+
 /// Convenience wrapper around `std::Result`.
 pub type Result<T> = result::Result<T, Error>;
 
@@ -23,10 +27,70 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         self.inner.get_context()
     }
+
+    /// Whether the error is transient, so sending
+    /// again later may succeed: network errors, 5xx
+    /// responses and rate limiting by SocketLabs.
+    pub fn is_retryable(&self) -> bool {
+        match *self.kind() {
+            ErrorKind::RequestError(_) | ErrorKind::Timeout => true,
+            ErrorKind::HttpError(status) => status >= 500,
+            _ => self.is_quota(),
+        }
+    }
+
+    /// Whether SocketLabs refused the credentials
+    /// or the account they belong to.
+    pub fn is_auth(&self) -> bool {
+        match *self.kind() {
+            ErrorKind::HttpError(401) | ErrorKind::HttpError(403) => true,
+            ErrorKind::InjectionFailed(error_code) => error_code.is_auth(),
+            _ => false,
+        }
+    }
+
+    /// Whether SocketLabs rate limited the request
+    /// or the account is over its quota.
+    pub fn is_quota(&self) -> bool {
+        matches!(
+            *self.kind(),
+            ErrorKind::RateLimited(_) | ErrorKind::InjectionFailed(PostMessageErrorCode::OverQuota)
+        )
+    }
+
+    /// Whether the messages themselves are at fault, as found
+    /// either before sending or by SocketLabs. Sending them
+    /// again as they are fails the same way.
+    pub fn is_client_error(&self) -> bool {
+        match *self.kind() {
+            ErrorKind::HttpError(status) => (400..500).contains(&status) && !self.is_auth(),
+            ErrorKind::InjectionFailed(error_code) => matches!(
+                error_code,
+                PostMessageErrorCode::InvalidData
+                    | PostMessageErrorCode::NoMessages
+                    | PostMessageErrorCode::EmptyMessage
+                    | PostMessageErrorCode::TooManyMessages
+                    | PostMessageErrorCode::TooManyRecipients
+                    | PostMessageErrorCode::NoValidRecipients
+            ),
+            ErrorKind::MessageParsingError(_)
+            | ErrorKind::InvalidAttachmentPath(_)
+            | ErrorKind::InvalidMergeData(_)
+            | ErrorKind::InvalidBody(_)
+            | ErrorKind::TemplateError(_)
+            | ErrorKind::InvalidCharset(_)
+            | ErrorKind::InvalidEmail(_)
+            | ErrorKind::TooManyMessages(_)
+            | ErrorKind::TooManyRecipients(..)
+            | ErrorKind::InvalidMessage(..)
+            | ErrorKind::MessageRejected(_) => true,
+            _ => false,
+        }
+    }
 }
 
 impl Fail for Error {
-    fn cause(&self) -> Option<&Fail> {
+    fn cause(&self) -> Option<&dyn Fail> {
         self.inner.cause()
     }
 
@@ -43,20 +107,107 @@ impl fmt::Display for Error {
 
 /// The different kids of application errors
 /// this crate may return.
-#[derive(Debug, Fail)]
+#[derive(Clone, Debug, Fail)]
 pub enum ErrorKind {
     #[fail(display = "Error parsing message {}", _0)]
     MessageParsingError(String),
     #[fail(display = "{}", _0)]
     RequestError(String),
+    #[fail(display = "The request to SocketLabs timed out.")]
+    Timeout,
+    #[fail(display = "SocketLabs responded with HTTP status {}.", _0)]
+    HttpError(u16),
+    #[fail(display = "SocketLabs rate limited the request.")]
+    RateLimited(Option<Duration>),
+    #[fail(display = "Not sending while the circuit breaker is open.")]
+    CircuitOpen,
+    #[fail(display = "The background sender was shut down.")]
+    SenderClosed,
+    #[fail(display = "No client for tenant {}.", _0)]
+    UnknownTenant(String),
+    #[fail(display = "Invalid configuration: {}", _0)]
+    ConfigError(String),
+    #[fail(display = "Error getting credentials: {}", _0)]
+    CredentialsError(String),
     #[fail(display = "Server redirecting too many times or making loop.")]
     TooManyRedirects,
+    #[fail(display = "Error reading file: {}", _0)]
+    IoError(String),
+    #[fail(display = "Error accessing the message queue: {}", _0)]
+    QueueError(String),
+    #[fail(display = "Not a valid attachment file: {}", _0)]
+    InvalidAttachmentPath(String),
+    #[fail(display = "Invalid merge data: {}", _0)]
+    InvalidMergeData(String),
+    #[fail(display = "Error rendering message body: {}", _0)]
+    InvalidBody(String),
+    #[fail(display = "Error rendering template: {}", _0)]
+    TemplateError(String),
+    #[fail(display = "Invalid charset name: {}", _0)]
+    InvalidCharset(String),
+    #[fail(display = "Invalid certificate: {}", _0)]
+    InvalidCertificate(String),
+    #[fail(display = "Invalid URL: {}", _0)]
+    InvalidUrl(String),
+    #[fail(display = "Invalid HTTP header: {}", _0)]
+    InvalidHttpHeader(String),
+    #[fail(display = "Invalid email address: {}", _0)]
+    InvalidEmail(String),
+    #[fail(display = "{} messages in a single request, more than allowed.", _0)]
+    TooManyMessages(usize),
+    #[fail(
+        display = "Message at index {} has {} recipients, more than allowed.",
+        _0, _1
+    )]
+    TooManyRecipients(usize, usize),
+    #[fail(display = "Message at index {} is invalid: {:?}", _0, _1)]
+    InvalidMessage(usize, Vec<ValidationError>),
+    #[fail(display = "SocketLabs rejected the injection request: {}", _0)]
+    InjectionFailed(PostMessageErrorCode),
+    #[fail(display = "SocketLabs rejected the message: {}", _0)]
+    MessageRejected(MessageResultErrorCode),
+    #[fail(display = "Only some messages were sent: {}", _0)]
+    PartiallySent(Box<PartialResponse>),
     #[fail(
         display = "Unexpected error. Please file a bug at: https://github.com/brizental/socketlabs-rs/issues"
     )]
     UnexpectedError,
 }
 
+/// The problems found in a message by client-side
+/// validation, before sending it to SocketLabs.
+#[derive(Clone, Debug, Fail, PartialEq)]
+pub enum ValidationError {
+    #[fail(display = "This message does not contain a valid From address.")]
+    InvalidFromAddress,
+    #[fail(display = "This message does not contain a To address.")]
+    EmptyToAddress,
+    #[fail(display = "This message has an invalid recipient address: {}", _0)]
+    InvalidRecipientAddress(String),
+    #[fail(display = "This message has {} recipients, more than allowed.", _0)]
+    TooManyRecipients(usize),
+    #[fail(display = "This message contains an empty subject line.")]
+    EmptySubject,
+    #[fail(display = "This message does not have a text or HTML body, nor an API Template.")]
+    NoValidBodyParts,
+    #[fail(
+        display = "The Html Body, Text Body and Amp Body cannot be set when also specifying an API Template ID."
+    )]
+    MessageBodyConflict,
+    #[fail(display = "DeliveryAddress is reserved and cannot be a global merge field.")]
+    ReservedMergeField,
+    #[fail(
+        display = "Per-message merge data at index {} has no DeliveryAddress.",
+        _0
+    )]
+    MissingDeliveryAddress(usize),
+    #[fail(
+        display = "Attachments add up to {} bytes at {}, more than allowed.",
+        _1, _0
+    )]
+    AttachmentsTooLarge(String, usize),
+}
+
 impl From<ErrorKind> for Error {
     fn from(kind: ErrorKind) -> Error {
         Context::new(kind).into()
@@ -69,26 +220,27 @@ impl From<Context<ErrorKind>> for Error {
     }
 }
 
+#[cfg(feature = "reqwest")]
 impl From<ReqwestError> for Error {
     fn from(error: ReqwestError) -> Error {
-        if error.is_http() {
-            return match error.url() {
-                None => ErrorKind::UnexpectedError.into(),
-                Some(_) => ErrorKind::RequestError(
-                    "Problem making request to SocketLabs.".to_string(),
-                ).into(),
-            };
+        if error.is_redirect() {
+            return ErrorKind::TooManyRedirects.into();
         }
 
-        if error.is_serialization() {
+        if error.is_builder() {
             return ErrorKind::UnexpectedError.into();
         }
 
-        if error.is_redirect() {
-            return ErrorKind::TooManyRedirects.into();
+        if error.is_timeout() {
+            return ErrorKind::Timeout.into();
         }
 
-        ErrorKind::UnexpectedError.into()
+        match error.url() {
+            None => ErrorKind::UnexpectedError.into(),
+            Some(_) => {
+                ErrorKind::RequestError("Problem making request to SocketLabs.".to_string()).into()
+            }
+        }
     }
 }
 
@@ -97,3 +249,34 @@ impl From<SerdeError> for Error {
         ErrorKind::MessageParsingError(error.to_string()).into()
     }
 }
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        ErrorKind::IoError(error.to_string()).into()
+    }
+}
+
+#[cfg(feature = "persistence")]
+impl From<sled::Error> for Error {
+    fn from(error: sled::Error) -> Error {
+        ErrorKind::QueueError(error.to_string()).into()
+    }
+}
+
+#[cfg(feature = "ureq")]
+impl From<ureq::Error> for Error {
+    fn from(error: ureq::Error) -> Error {
+        match error {
+            ureq::Error::Timeout(_) => ErrorKind::Timeout.into(),
+            ureq::Error::TooManyRedirects => ErrorKind::TooManyRedirects.into(),
+            error => ErrorKind::RequestError(error.to_string()).into(),
+        }
+    }
+}
+
+#[cfg(feature = "csv")]
+impl From<csv::Error> for Error {
+    fn from(error: csv::Error) -> Error {
+        ErrorKind::InvalidMergeData(error.to_string()).into()
+    }
+}
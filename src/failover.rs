@@ -0,0 +1,204 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Failover from a primary to a secondary SocketLabs server
+//! when the primary refuses its credentials or keeps failing.
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::client::SocketLabsClient;
+use crate::credentials::Credentials;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::error::ErrorKind;
+use crate::error::Result;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::message::Message;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::request::MAX_MESSAGES_PER_REQUEST;
+use crate::response::Response;
+#[cfg(feature = "reqwest")]
+use crate::transport::ReqwestTransport;
+use crate::transport::Transport;
+#[cfg(not(feature = "reqwest"))]
+use crate::transport::UreqTransport;
+
+/// Which of the credentials of a `FailoverClient` were sent with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Credential {
+    /// The primary credentials.
+    Primary,
+    /// The secondary credentials, after failing over.
+    Secondary,
+}
+
+/// The outcome of messages sent with a `FailoverClient`.
+#[derive(Debug)]
+pub struct FailoverResponse {
+    /// The response of SocketLabs, as `SocketLabsClient::send`
+    /// returns, failing with `ErrorKind::PartiallySent` when only
+    /// some of the injection requests went through, even over both
+    /// servers, so that their responses are not lost.
+    pub result: Result<Response<'static>>,
+    /// `Credential::Secondary` if the client failed over while
+    /// sending, even when earlier injection requests of the
+    /// same messages were sent with the primary credentials.
+    pub credential: Credential,
+}
+
+/// A client sending with primary credentials and failing over to
+/// secondary ones, such as those of a backup SocketLabs server.
+///
+/// An injection request is sent again with the secondary credentials
+/// when the primary ones are refused, such as with `InvalidAuthentication`
+/// or `AccountDisabled`, or when it still fails with a 5xx response
+/// after the retries of the primary client, or its circuit breaker is
+/// open. The remaining requests of the same messages then go straight
+/// to the secondary server, the next messages try the primary first.
+///
+/// Messages are sent one injection request after the other, so that
+/// none is sent twice. Messages which could not be sent are given to
+/// the dead letter sink of the client which made the last attempt,
+/// so not to the primary one when they are failed over.
+#[derive(Debug)]
+pub struct FailoverClient {
+    primary: SocketLabsClient,
+    secondary: SocketLabsClient,
+}
+
+impl FailoverClient {
+    /// Creates a new client failing over from `primary` to
+    /// `secondary`, sending over the same pooled connections.
+    pub fn new(primary: Credentials, secondary: Credentials) -> FailoverClient {
+        #[cfg(feature = "reqwest")]
+        let transport: Arc<dyn Transport> = Arc::new(ReqwestTransport::new());
+        #[cfg(not(feature = "reqwest"))]
+        let transport: Arc<dyn Transport> = Arc::new(UreqTransport::new());
+
+        let mut primary = SocketLabsClient::with_credentials(primary);
+        primary.set_transport(Arc::clone(&transport));
+        let mut secondary = SocketLabsClient::with_credentials(secondary);
+        secondary.set_transport(transport);
+
+        FailoverClient::with_clients(primary, secondary)
+    }
+
+    /// Creates a new client failing over from `primary` to `secondary`,
+    /// such as clients with their own retries or base URLs.
+    pub fn with_clients(primary: SocketLabsClient, secondary: SocketLabsClient) -> FailoverClient {
+        FailoverClient { primary, secondary }
+    }
+
+    /// The client sending with the primary credentials.
+    pub fn primary(&self) -> &SocketLabsClient {
+        &self.primary
+    }
+
+    /// The client sending with the secondary credentials.
+    pub fn secondary(&self) -> &SocketLabsClient {
+        &self.secondary
+    }
+
+    /// Sends the given messages, in as many injection
+    /// requests as needed, failing over if needed.
+    #[cfg(feature = "blocking")]
+    pub fn send(&self, messages: &[Message]) -> FailoverResponse {
+        let mut credential = Credential::Primary;
+        let mut results = Vec::new();
+        for (index, chunk) in messages.chunks(MAX_MESSAGES_PER_REQUEST).enumerate() {
+            let mut sent = self.client(credential).send_requests(chunk);
+            if credential == Credential::Primary && fails_over(&sent) {
+                log_debug!("Failing over to the secondary credentials");
+                credential = Credential::Secondary;
+                sent = self.secondary.send_requests(chunk);
+            }
+            self.settle(credential, index, chunk, sent, &mut results);
+        }
+
+        FailoverResponse {
+            result: Response::combine(results),
+            credential,
+        }
+    }
+
+    /// Sends the given messages without blocking the current
+    /// thread. See `FailoverClient::send`.
+    #[cfg(feature = "async")]
+    pub async fn send_async(&self, messages: &[Message<'_>]) -> FailoverResponse {
+        let mut credential = Credential::Primary;
+        let mut results = Vec::new();
+        for (index, chunk) in messages.chunks(MAX_MESSAGES_PER_REQUEST).enumerate() {
+            let mut sent = self.client(credential).send_requests_async(chunk).await;
+            if credential == Credential::Primary && fails_over(&sent) {
+                log_debug!("Failing over to the secondary credentials");
+                credential = Credential::Secondary;
+                sent = self.secondary.send_requests_async(chunk).await;
+            }
+            self.settle(credential, index, chunk, sent, &mut results);
+        }
+
+        FailoverResponse {
+            result: Response::combine(results),
+            credential,
+        }
+    }
+
+    /// Dead letters the messages of chunk number `index` which could
+    /// not be sent with `credential`, and adds the results of its
+    /// injection requests to `results`, pointing to the original
+    /// positions of the messages.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn settle(
+        &self,
+        credential: Credential,
+        index: usize,
+        chunk: &[Message],
+        sent: Result<Vec<(Range<usize>, Result<Response<'static>>)>>,
+        results: &mut Vec<(Range<usize>, Result<Response<'static>>)>,
+    ) {
+        let offset = index * MAX_MESSAGES_PER_REQUEST;
+        let sent = match sent {
+            Ok(sent) => sent,
+            Err(error) => {
+                results.push((offset..offset + chunk.len(), Err(error)));
+                return;
+            }
+        };
+        for (indices, result) in sent {
+            self.client(credential)
+                .dead_letter(&chunk[indices.clone()], &result);
+            results.push((offset + indices.start..offset + indices.end, result));
+        }
+    }
+
+    /// The client sending with `credential`.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn client(&self, credential: Credential) -> &SocketLabsClient {
+        match credential {
+            Credential::Primary => &self.primary,
+            Credential::Secondary => &self.secondary,
+        }
+    }
+}
+
+/// Whether the injection requests sent with the primary
+/// credentials call for the secondary ones.
+#[cfg(any(feature = "async", feature = "blocking"))]
+fn fails_over(sent: &Result<Vec<(Range<usize>, Result<Response<'static>>)>>) -> bool {
+    let sent = match sent {
+        Ok(sent) => sent,
+        Err(_) => return false,
+    };
+    sent.iter().any(|(_, result)| match result {
+        Ok(response) => response.error_code.is_auth(),
+        Err(error) => {
+            error.is_auth()
+                || matches!(
+                    *error.kind(),
+                    ErrorKind::HttpError(500..=599) | ErrorKind::CircuitOpen
+                )
+        }
+    })
+}
@@ -0,0 +1,85 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Helpers to prepare html bodies for email clients.
+
+use kuchikiki::traits::TendrilSink;
+
+/// Inlines the rules of the `css` stylesheet into
+/// the `style` attribute of every matching element.
+///
+/// Rules are applied in source order, without taking selector
+/// specificity into account, and declarations already inline in
+/// the html take precedence. At-rules such as `@media` and
+/// selectors which can't be matched statically, such as
+/// `:hover`, are left out.
+pub fn inline_css(html: &str, css: &str) -> String {
+    let document = kuchikiki::parse_html().one(html);
+
+    // Prepending the rules in reverse leaves them in source order,
+    // followed by the declarations originally in the html.
+    for (selectors, declarations) in parse_rules(css).into_iter().rev() {
+        let elements = match document.select(selectors) {
+            Ok(elements) => elements,
+            Err(()) => continue,
+        };
+
+        for element in elements {
+            let mut attributes = element.attributes.borrow_mut();
+            let style = match attributes.get("style") {
+                Some(style) => format!("{}; {}", declarations, style),
+                None => declarations.to_string(),
+            };
+            attributes.insert("style", style);
+        }
+    }
+
+    document.to_string()
+}
+
+/// Splits a stylesheet into its top-level `(selectors, declarations)`
+/// rules, skipping comments and at-rules.
+fn parse_rules(css: &str) -> Vec<(&str, &str)> {
+    let mut rules = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut open = 0;
+    let mut chars = css.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '/' if chars.peek().map(|&(_, next)| next) == Some('*') => {
+                let end = css[index + 2..]
+                    .find("*/")
+                    .map_or(css.len(), |end| index + 2 + end + 2);
+                while chars.peek().is_some_and(|&(next, _)| next < end) {
+                    chars.next();
+                }
+                if depth == 0 {
+                    start = end;
+                }
+            }
+            '{' => {
+                if depth == 0 {
+                    open = index;
+                }
+                depth += 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    let selectors = css[start..open].trim();
+                    let declarations = css[open + 1..index].trim().trim_end_matches(';');
+                    if !selectors.starts_with('@') && !declarations.is_empty() {
+                        rules.push((selectors, declarations.trim()));
+                    }
+                    start = index + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    rules
+}
@@ -0,0 +1,101 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Interoperability with [lettre](https://lettre.rs/), so applications
+//! which already build their emails with it can send them through SocketLabs.
+
+use std::convert::TryFrom;
+
+use async_trait::async_trait;
+use lettre::address::Envelope;
+use lettre::AsyncTransport;
+#[cfg(feature = "blocking")]
+use lettre::Transport;
+
+use crate::client::SocketLabsClient;
+use crate::error::{Error, Result};
+use crate::message::Message;
+use crate::response::Response;
+
+impl TryFrom<lettre::Message> for Message<'static> {
+    type Error = Error;
+
+    /// Converts the formatted lettre message, see `Message::from_eml`.
+    ///
+    /// Bcc'd recipients, which lettre only keeps in the envelope,
+    /// are added back from it, as is the sender when the message
+    /// has no From header.
+    fn try_from(message: lettre::Message) -> Result<Message<'static>> {
+        convert(message.envelope(), &message.formatted())
+    }
+}
+
+/// A lettre transport which sends every email
+/// through the Injection API as a single message.
+///
+/// Sending returns the SocketLabs response as is, whose
+/// `error_code` should be checked for rejected messages.
+#[derive(Debug)]
+pub struct SocketLabsTransport {
+    client: SocketLabsClient,
+}
+
+impl SocketLabsTransport {
+    /// Creates a new transport sending through `client`.
+    pub fn new(client: SocketLabsClient) -> SocketLabsTransport {
+        SocketLabsTransport { client }
+    }
+}
+
+impl From<SocketLabsClient> for SocketLabsTransport {
+    fn from(client: SocketLabsClient) -> SocketLabsTransport {
+        SocketLabsTransport::new(client)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl Transport for SocketLabsTransport {
+    type Ok = Response<'static>;
+    type Error = Error;
+
+    /// Blocks the current thread, see `SocketLabsClient::send`.
+    fn send_raw(&self, envelope: &Envelope, email: &[u8]) -> Result<Response<'static>> {
+        self.client.send(&[convert(envelope, email)?])
+    }
+}
+
+#[async_trait]
+impl AsyncTransport for SocketLabsTransport {
+    type Ok = Response<'static>;
+    type Error = Error;
+
+    async fn send_raw(&self, envelope: &Envelope, email: &[u8]) -> Result<Response<'static>> {
+        let message = convert(envelope, email)?;
+        self.client.send_async(&[message]).await
+    }
+}
+
+/// Converts a formatted email along with its envelope.
+fn convert(envelope: &Envelope, email: &[u8]) -> Result<Message<'static>> {
+    let mut converted = Message::from_eml(email)?;
+
+    for address in envelope.to() {
+        let listed = converted
+            .to()
+            .iter()
+            .chain(converted.cc())
+            .chain(converted.bcc())
+            .any(|email| email.address().eq_ignore_ascii_case(address.as_ref()));
+        if !listed {
+            converted.add_bcc(address.to_string(), None);
+        }
+    }
+    if converted.from().address().is_empty() {
+        if let Some(from) = envelope.from() {
+            converted.set_from(from.to_string(), None);
+        }
+    }
+
+    Ok(converted)
+}
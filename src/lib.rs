@@ -19,17 +19,172 @@
 //! * Inbound
 //! * Reporting
 //! * On-Demand
+//!
+//! # WebAssembly
+//!
+//! On `wasm32-unknown-unknown`, such as in Cloudflare Workers, only
+//! the async API is available, so the `blocking` feature must be
+//! disabled. Requests go through the `fetch` API of the runtime,
+//! which ignores most of `transport::HttpOptions`, and neither
+//! `background` nor `service` are available.
+//!
+//! # Features
+//!
+//! * `async`: enabled by default, sends messages without
+//!   blocking the current thread, from within a Tokio runtime.
+//! * `blocking`: enabled by default, sends messages
+//!   blocking the current thread.
+//! * `default-tls`: enabled by default, connects to SocketLabs
+//!   with the TLS backend of the platform, such as OpenSSL.
+//! * `reqwest`: enabled by the TLS features, sends messages
+//!   with `reqwest`, see `transport::ReqwestTransport`.
+//! * `rustls-tls`: connects to SocketLabs with `rustls` instead,
+//!   for builds without OpenSSL. Disable the default features
+//!   along with enabling it.
+//! * `logging`: emits `log` records at debug level around every
+//!   injection request. Credentials are always redacted.
+//! * `csv`: loads per-message merge data from CSV files,
+//!   see `merge::MergeRows::from_csv`.
+//! * `eml`: imports messages from raw RFC 822 documents,
+//!   see `message::Message::from_eml`.
+//! * `gzip`: compresses large request bodies, see
+//!   `ClientBuilder::gzip_threshold`.
+//! * `handlebars`: renders Handlebars templates into message bodies,
+//!   see `message::Message::render_body`.
+//! * `html2text`: derives a text body from an html body,
+//!   see `message::Message::set_html_with_fallback`.
+//! * `inline-css`: inlines stylesheets into html bodies,
+//!   see `html::inline_css`.
+//! * `idna`: converts internationalized domains in email
+//!   addresses to punycode when serializing messages.
+//! * `infer`: detects the content type of attachments from
+//!   their content, see `message::Attachment::from_bytes`.
+//! * `lettre`: converts messages built with `lettre` through
+//!   `TryFrom` and sends them with `lettre::SocketLabsTransport`.
+//!   Implies `eml`.
+//! * `markdown`: renders Markdown into message bodies,
+//!   see `message::Message::set_markdown`.
+//! * `persistence`: keeps unsent messages in a queue on disk,
+//!   see `queue::PersistentQueue`.
+//! * `tera`: renders Tera templates into message bodies and
+//!   subjects, see `message::Message::render_tera_body`.
+//! * `testing`: mocks SocketLabs with canned responses, or records
+//!   and replays real ones, see `testing::MockTransport`.
+//! * `toml`: reads client settings from TOML files,
+//!   see `config::ClientConfig::from_path`.
+//! * `tower`: sends messages through a `tower::Service`,
+//!   see `service::SocketLabsService`.
+//! * `ureq`: a lightweight blocking transport sending with
+//!   `ureq`, see `transport::UreqTransport`. It is the default
+//!   transport when `reqwest` is disabled.
+//! * `zeroize`: wipes api keys from memory when the client or
+//!   request holding them is dropped, see `secret::SecretString`.
+//! * `messages-only`: enables nothing by itself, use it with
+//!   `default-features = false` and neither `reqwest` nor `ureq`.
+//!   Such builds leave out the modules sending messages, such as
+//!   `client`, `transport` and `retry`, along with their HTTP and
+//!   client dependencies, keeping `message`, `response`, and
+//!   `request` and `schema` to serialize and check injection
+//!   payloads.
+
+// The `Fail` derive from `failure` expands into non-local impl blocks.
+#![allow(non_local_definitions)]
+
+#[cfg(all(target_arch = "wasm32", feature = "blocking"))]
+compile_error!("The `blocking` feature is not supported on wasm32, disable the default features.");
 
 extern crate failure;
 #[macro_use]
 extern crate failure_derive;
+#[cfg(all(
+    feature = "logging",
+    any(feature = "reqwest", feature = "ureq"),
+    any(feature = "async", feature = "blocking", feature = "ureq")
+))]
+#[macro_use]
+extern crate log;
+#[cfg(feature = "reqwest")]
 extern crate reqwest;
 extern crate serde;
 extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
 
+/// Logs at debug level when the `logging` feature is enabled,
+/// expands to nothing otherwise.
+#[cfg(all(
+    feature = "logging",
+    any(feature = "reqwest", feature = "ureq"),
+    any(feature = "async", feature = "blocking", feature = "ureq")
+))]
+macro_rules! log_debug {
+    ($($arg:tt)*) => (debug!($($arg)*));
+}
+
+#[cfg(all(
+    not(feature = "logging"),
+    any(feature = "reqwest", feature = "ureq"),
+    any(feature = "async", feature = "blocking", feature = "ureq")
+))]
+macro_rules! log_debug {
+    ($($arg:tt)*) => {};
+}
+
+#[cfg(all(
+    feature = "async",
+    any(feature = "reqwest", feature = "ureq"),
+    not(target_arch = "wasm32")
+))]
+pub mod background;
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+pub mod breaker;
+pub mod builder;
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+pub mod client;
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+pub mod config;
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+pub mod credentials;
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+pub mod dead_letter;
+pub mod eml;
+pub mod encoding;
 pub mod error;
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+pub mod failover;
+#[cfg(feature = "inline-css")]
+pub mod html;
+#[cfg(all(feature = "lettre", any(feature = "reqwest", feature = "ureq")))]
+pub mod lettre;
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+pub mod limiter;
+pub mod merge;
 pub mod message;
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+pub mod multi;
+#[cfg(all(feature = "persistence", any(feature = "reqwest", feature = "ureq")))]
+pub mod queue;
 pub mod request;
 pub mod response;
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+pub mod retry;
+pub mod schema;
+pub mod secret;
+#[cfg(all(
+    feature = "tower",
+    any(feature = "reqwest", feature = "ureq"),
+    not(target_arch = "wasm32")
+))]
+pub mod service;
+#[cfg(any(feature = "handlebars", feature = "tera"))]
+pub mod template;
+#[cfg(all(feature = "testing", any(feature = "reqwest", feature = "ureq")))]
+pub mod testing;
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+pub mod transport;
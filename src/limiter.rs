@@ -0,0 +1,102 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A client-side rate limiter, keeping senders under
+//! the limits of their SocketLabs plan.
+
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// A token-bucket rate limiter, throttling how many messages
+/// and injection requests are sent per second.
+///
+/// Each bucket holds up to one second worth of tokens, so short
+/// bursts are let through right away. Requests are never split to
+/// fit a bucket, those over it wait until enough tokens refilled.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    messages: Option<Mutex<Bucket>>,
+    requests: Option<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// How many tokens are added every second.
+    rate: f64,
+    /// How many tokens are left, negative when
+    /// they are already reserved by pending requests.
+    tokens: f64,
+    /// When tokens were last added.
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a new rate limiter, not limiting anything until
+    /// either `set_messages_per_second` or `set_requests_per_second`
+    /// is called.
+    pub fn new() -> RateLimiter {
+        RateLimiter::default()
+    }
+
+    /// Sets how many messages may be sent per second.
+    pub fn set_messages_per_second(&mut self, rate: u32) {
+        self.messages = Some(Mutex::new(Bucket::new(rate)))
+    }
+
+    /// Sets how many injection requests may be sent per second.
+    pub fn set_requests_per_second(&mut self, rate: u32) {
+        self.requests = Some(Mutex::new(Bucket::new(rate)))
+    }
+
+    /// Reserves the tokens for an injection request of `messages`
+    /// messages, returning how long to wait before sending it.
+    pub(crate) fn reserve(&self, messages: usize) -> Duration {
+        let reserve = |bucket: &Option<Mutex<Bucket>>, tokens: usize| {
+            bucket.as_ref().map_or(Duration::ZERO, |bucket| {
+                bucket
+                    .lock()
+                    .unwrap_or_else(|error| error.into_inner())
+                    .reserve(tokens as f64)
+            })
+        };
+
+        let delay = reserve(&self.messages, messages).max(reserve(&self.requests, 1));
+        if !delay.is_zero() {
+            log_debug!("Rate limiting the injection request for {:?}", delay);
+        }
+
+        delay
+    }
+}
+
+impl Bucket {
+    fn new(rate: u32) -> Bucket {
+        let rate = f64::from(rate.max(1));
+        Bucket {
+            rate,
+            tokens: rate,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    /// Takes `tokens` tokens, returning how long
+    /// it takes until the bucket holds them.
+    fn reserve(&mut self, tokens: f64) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate) - tokens;
+        self.refilled_at = now;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
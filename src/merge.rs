@@ -0,0 +1,113 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Helpers to build the per-message data for the
+//! SocketLabs inline Merge feature in bulk.
+
+#[cfg(feature = "csv")]
+use std::io::Read;
+
+#[cfg(feature = "csv")]
+use crate::error::{ErrorKind, Result};
+use crate::message::Message;
+
+/// Column names recognized as the recipient address,
+/// compared case-insensitively.
+#[cfg(feature = "csv")]
+const ADDRESS_COLUMNS: &[&str] = &["DeliveryAddress", "Email", "EmailAddress", "Email_Address"];
+
+/// A list of per-message merge rows, one per recipient.
+///
+/// Every row holds the reserved `DeliveryAddress` field
+/// followed by the other merge fields of that recipient.
+#[derive(Clone, Debug, Default)]
+pub struct MergeRows {
+    rows: Vec<Vec<(String, String)>>,
+}
+
+impl MergeRows {
+    /// Reads merge rows from CSV data.
+    ///
+    /// The header row defines the merge field names. The column named
+    /// `DeliveryAddress`, `Email`, `EmailAddress` or `Email_Address`
+    /// (in any case) holds the recipient address and is mapped to
+    /// `DeliveryAddress`.
+    #[cfg(feature = "csv")]
+    pub fn from_csv<R: Read>(reader: R) -> Result<MergeRows> {
+        let mut reader = csv::Reader::from_reader(reader);
+        let mut headers: Vec<String> = reader.headers()?.iter().map(String::from).collect();
+
+        let address = headers
+            .iter()
+            .position(|header| {
+                ADDRESS_COLUMNS
+                    .iter()
+                    .any(|column| column.eq_ignore_ascii_case(header.trim()))
+            })
+            .ok_or_else(|| {
+                ErrorKind::InvalidMergeData("CSV data has no email address column".to_string())
+            })?;
+        headers[address] = "DeliveryAddress".to_string();
+
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            let mut row = vec![(headers[address].clone(), record[address].to_string())];
+            row.extend(
+                headers
+                    .iter()
+                    .zip(record.iter())
+                    .enumerate()
+                    .filter(|&(index, _)| index != address)
+                    .map(|(_, (field, value))| (field.clone(), value.to_string())),
+            );
+            rows.push(row);
+        }
+
+        Ok(MergeRows { rows })
+    }
+
+    /// Adds a row for the recipient at `address` with the given merge fields.
+    pub fn push<A, K, V, I>(&mut self, address: A, fields: I)
+    where
+        A: Into<String>,
+        K: Into<String>,
+        V: Into<String>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut row = vec![("DeliveryAddress".to_string(), address.into())];
+        row.extend(
+            fields
+                .into_iter()
+                .map(|(field, value)| (field.into(), value.into())),
+        );
+        self.rows.push(row);
+    }
+
+    /// The number of rows, that is, the number of recipients.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Whether there are no rows.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Adds every row to the per-message merge data of `message`.
+    pub fn apply_to(self, message: &mut Message) {
+        for row in self.rows {
+            message.add_per_message_merge(row);
+        }
+    }
+}
+
+impl IntoIterator for MergeRows {
+    type Item = Vec<(String, String)>;
+    type IntoIter = std::vec::IntoIter<Vec<(String, String)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows.into_iter()
+    }
+}
@@ -6,20 +6,54 @@
 //! message for SocketLabs [Injection API](https://www.socketlabs.com/api-reference/injection-api/).
 
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::hash::Hash;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use base64::display::Base64Display;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::encoding::encode_word;
+use crate::error::{Error, ErrorKind, Result, ValidationError};
+
+/// The maximum number of recipients, across To, Cc and Bcc,
+/// accepted by SocketLabs in a single message.
+pub const MAX_RECIPIENTS_PER_MESSAGE: usize = 50;
+
+/// The maximum size, in bytes, of the base64 encoded
+/// attachments of a single message.
+pub const MAX_ATTACHMENTS_SIZE: usize = 10 * 1024 * 1024;
+
+/// The merge field reserved by SocketLabs to
+/// hold the recipient of each merged message.
+const DELIVERY_ADDRESS: &str = "DeliveryAddress";
+
+/// A message which owns all of its data, so it can
+/// be stored or moved across threads freely.
+pub type OwnedMessage = Message<'static>;
 
 /// This is a representation of email attachments
 /// that corresponds to the way SocketLabs represents them.
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
-struct Attachment<'a> {
+pub struct Attachment<'a> {
     /// The name of the attachment
     name: Cow<'a, str>,
-    /// A description of the content in the attachment
-    content: Cow<'a, str>,
+    /// The content of the attachment, only
+    /// base64 encoded when serializing it
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    content: Vec<u8>,
     /// The id of the content in the attachment
-    content_id: Cow<'a, str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_id: Option<Cow<'a, str>>,
     /// The type of the content in the attachment
     content_type: Cow<'a, str>,
     /// The headers in the attachment
@@ -27,23 +61,178 @@ struct Attachment<'a> {
     custom_headers: Option<Vec<CustomHeader<'a>>>,
 }
 
+impl<'a> Attachment<'a> {
+    /// Create a new Attachment with the given content.
+    pub fn new<N: Into<Cow<'a, str>>, T: Into<Cow<'a, str>>, B: Into<Vec<u8>>>(
+        name: N,
+        content_type: T,
+        content: B,
+    ) -> Attachment<'a> {
+        Attachment {
+            name: name.into(),
+            content: content.into(),
+            content_id: None,
+            content_type: content_type.into(),
+            custom_headers: None,
+        }
+    }
+
+    /// Create a new Attachment from the file at `path`.
+    ///
+    /// The name of the attachment is the file name and the
+    /// content type is guessed from the file extension, falling
+    /// back to `application/octet-stream`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Attachment<'static>> {
+        let path = path.as_ref();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| ErrorKind::InvalidAttachmentPath(path.display().to_string()))?
+            .to_string();
+        let content_type = mime_guess::from_path(path)
+            .first_or_octet_stream()
+            .to_string();
+        let content = fs::read(path)?;
+
+        Ok(Attachment::new(name, content_type, content))
+    }
+
+    /// Create a new Attachment, detecting its content type.
+    ///
+    /// With the `infer` feature, the content type is sniffed from
+    /// the magic bytes of `content`, such as for PNG, JPEG, PDF or
+    /// zip files. Otherwise, or when sniffing fails, it is guessed
+    /// from the extension of `name`, falling back to
+    /// `application/octet-stream`.
+    pub fn from_bytes<T: Into<Cow<'a, str>>, B: Into<Vec<u8>>>(
+        name: T,
+        content: B,
+    ) -> Attachment<'a> {
+        let name = name.into();
+        let content = content.into();
+
+        let content_type = match sniff_content_type(&content) {
+            Some(content_type) => content_type.to_string(),
+            None => mime_guess::from_path(name.as_ref())
+                .first_or_octet_stream()
+                .to_string(),
+        };
+
+        Attachment::new(name, content_type, content)
+    }
+
+    /// Create a new Attachment with the content read from `reader`.
+    ///
+    /// The content is read as raw bytes and only base64
+    /// encoded when the message is serialized.
+    pub fn from_reader<N: Into<Cow<'a, str>>, T: Into<Cow<'a, str>>, R: Read>(
+        name: N,
+        content_type: T,
+        mut reader: R,
+    ) -> Result<Attachment<'a>> {
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content)?;
+
+        Ok(Attachment::new(name, content_type, content))
+    }
+
+    /// Sets the id of the content in the attachment, used
+    /// to reference it from the html body as `cid:<content_id>`.
+    pub fn set_content_id<T: Into<Cow<'a, str>>>(&mut self, content_id: T) {
+        self.content_id = Some(content_id.into())
+    }
+
+    /// Adds a header to the attachment, such as a
+    /// `Content-Disposition` or a `Content-Description`.
+    pub fn add_header<K: Into<Cow<'a, str>>, V: Into<Cow<'a, str>>>(&mut self, name: K, value: V) {
+        let header = CustomHeader {
+            name: name.into(),
+            value: value.into(),
+        };
+
+        match self.custom_headers {
+            Some(ref mut headers) => headers.push(header),
+            None => self.custom_headers = Some(vec![header]),
+        }
+    }
+
+    /// The name of the attachment.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The content of the attachment.
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    /// The id of the content in the attachment, if any.
+    pub fn content_id(&self) -> Option<&str> {
+        self.content_id.as_deref()
+    }
+
+    /// The type of the content in the attachment.
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    /// The headers in the attachment.
+    pub fn custom_headers(&self) -> &[CustomHeader<'a>] {
+        self.custom_headers.as_deref().unwrap_or_default()
+    }
+
+    /// Converts into an attachment which owns all of its data.
+    pub fn into_owned(self) -> Attachment<'static> {
+        Attachment {
+            name: owned(self.name),
+            content: self.content,
+            content_id: self.content_id.map(owned),
+            content_type: owned(self.content_type),
+            custom_headers: self
+                .custom_headers
+                .map(|headers| headers.into_iter().map(CustomHeader::into_owned).collect()),
+        }
+    }
+}
+
 /// This is a representation of email headers
 /// that corresponds to the way SocketLabs represents them.
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
-struct CustomHeader<'a> {
+pub struct CustomHeader<'a> {
     /// The name of the header
     name: Cow<'a, str>,
     /// The value of the header
     value: Cow<'a, str>,
 }
 
+impl<'a> CustomHeader<'a> {
+    /// The name of the header.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value of the header.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Converts into a header which owns all of its data.
+    pub fn into_owned(self) -> CustomHeader<'static> {
+        CustomHeader {
+            name: owned(self.name),
+            value: owned(self.value),
+        }
+    }
+}
+
 /// This is a representation of an email address
 /// plus the optional name of the owner of that address.
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Email<'a> {
     /// The actual email address
+    #[serde(serialize_with = "serialize_address")]
     email_address: Cow<'a, str>,
     /// The name of the owner of the address
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -53,53 +242,296 @@ pub struct Email<'a> {
 impl<'a> Email<'a> {
     pub fn new(email_address: Cow<'a, str>, friendly_name: Option<Cow<'a, str>>) -> Email<'a> {
         Email {
-            email_address: email_address,
-            friendly_name: friendly_name,
+            email_address,
+            friendly_name,
+        }
+    }
+
+    /// The actual email address.
+    pub fn address(&self) -> &str {
+        &self.email_address
+    }
+
+    /// The name of the owner of the address, if any.
+    pub fn friendly_name(&self) -> Option<&str> {
+        self.friendly_name.as_deref()
+    }
+
+    /// Converts into an email which owns all of its data.
+    pub fn into_owned(self) -> Email<'static> {
+        Email {
+            email_address: owned(self.email_address),
+            friendly_name: self.friendly_name.map(owned),
+        }
+    }
+
+    /// Create a new Email, checking that the address follows
+    /// the basic RFC 5321 `local@domain` syntax and length limits.
+    pub fn parse<T: Into<Cow<'a, str>>>(email_address: T) -> Result<Email<'a>> {
+        let email_address = email_address.into();
+        if !is_valid_address(&email_address) {
+            return Err(ErrorKind::InvalidEmail(email_address.into_owned()).into());
+        }
+
+        Ok(Email::new(email_address, None))
+    }
+
+    /// Create a new Email from the combined `Display Name <local@domain>`
+    /// form, or from a bare address. The address is checked as in `Email::parse`.
+    pub fn parse_mailbox<T: Into<Cow<'a, str>>>(mailbox: T) -> Result<Email<'a>> {
+        let (address, name) = split_mailbox(mailbox.into());
+        let mut email = Email::parse(address)?;
+        email.friendly_name = name;
+        Ok(email)
+    }
+
+    /// Create a new Email from the arguments taken by the `Message`
+    /// setters, splitting `address` in the combined
+    /// `Display Name <local@domain>` form when no name is given.
+    fn mailbox<T: Into<Cow<'a, str>>>(address: T, name: Option<T>) -> Email<'a> {
+        match name {
+            Some(name) => Email::new(address.into(), Some(name.into())),
+            None => {
+                let (address, name) = split_mailbox(address.into());
+                Email::new(address, name)
+            }
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Email<'a> {
+    type Error = Error;
+
+    fn try_from(email_address: &'a str) -> Result<Email<'a>> {
+        Email::parse(email_address)
+    }
+}
+
+/// Serializes an email address, converting an internationalized
+/// domain to punycode when the `idna` feature is enabled.
+fn serialize_address<S: Serializer>(
+    address: &str,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    #[cfg(feature = "idna")]
+    {
+        if let Some(at) = address.rfind('@') {
+            let domain = &address[at + 1..];
+            if !domain.is_ascii() {
+                if let Ok(domain) = idna::domain_to_ascii(domain) {
+                    return serializer.serialize_str(&format!("{}@{}", &address[..at], domain));
+                }
+            }
         }
     }
+
+    serializer.serialize_str(address)
+}
+
+/// Detects the content type of attachment content from its magic bytes.
+#[cfg(feature = "infer")]
+fn sniff_content_type(content: &[u8]) -> Option<&'static str> {
+    infer::get(content).map(|kind| kind.mime_type())
+}
+
+/// Content sniffing is only available with the `infer` feature.
+#[cfg(not(feature = "infer"))]
+fn sniff_content_type(_content: &[u8]) -> Option<&'static str> {
+    None
+}
+
+/// Serializes the content of an attachment as base64,
+/// without keeping the whole encoded content in memory.
+fn serialize_base64<S: Serializer>(
+    content: &[u8],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_str(&Base64Display::new(content, &STANDARD))
+}
+
+/// Deserializes the base64 encoded content of an attachment.
+fn deserialize_base64<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<u8>, D::Error> {
+    let content = Cow::<str>::deserialize(deserializer)?;
+    STANDARD
+        .decode(content.as_bytes())
+        .map_err(de::Error::custom)
+}
+
+/// The length of `content` once base64 encoded.
+fn base64_len(content: &[u8]) -> usize {
+    content.len().div_ceil(3) * 4
+}
+
+/// Splits a mailbox in the `Display Name <local@domain>` form
+/// into its address and name. Anything else is taken as an address.
+fn split_mailbox(mailbox: Cow<str>) -> (Cow<str>, Option<Cow<str>>) {
+    let start = mailbox.len() - mailbox.trim_start().len();
+    let end = mailbox.trim_end().len();
+    let trimmed = &mailbox[start..end];
+    let open = match trimmed.rfind('<') {
+        Some(open) if trimmed.ends_with('>') => start + open,
+        _ => return (mailbox, None),
+    };
+
+    let address = open + 1..end - 1;
+    // The display name, without surrounding whitespace and quotes.
+    let name = mailbox[start..open].trim_start();
+    let name_start = open - name.len();
+    let name = name.trim_end();
+    let unquoted = name.trim_start_matches('"');
+    let name_start = name_start + name.len() - unquoted.len();
+    let name_end = name_start + unquoted.trim_end_matches('"').len();
+    let name = if name_start < name_end {
+        Some(name_start..name_end)
+    } else {
+        None
+    };
+
+    match mailbox {
+        Cow::Borrowed(mailbox) => (
+            Cow::Borrowed(mailbox[address].trim()),
+            name.map(|name| Cow::Borrowed(&mailbox[name])),
+        ),
+        Cow::Owned(mailbox) => (
+            Cow::Owned(mailbox[address].trim().to_string()),
+            name.map(|name| Cow::Owned(mailbox[name].to_string())),
+        ),
+    }
+}
+
+/// Whether an address is a merge field placeholder, such as
+/// `%%DeliveryAddress%%`, only resolved by SocketLabs.
+fn is_merge_field(address: &str) -> bool {
+    address.len() > 4 && address.starts_with("%%") && address.ends_with("%%")
+}
+
+/// Checks an address against the RFC 5321 syntax, allowing
+/// non-ASCII domains which can be converted to punycode.
+fn is_valid_address(address: &str) -> bool {
+    let (local, domain) = match address.rfind('@') {
+        Some(at) => (&address[..at], &address[at + 1..]),
+        None => return false,
+    };
+
+    if address.len() > 254 || local.is_empty() || local.len() > 64 || domain.is_empty() {
+        return false;
+    }
+
+    let valid_local = if local.len() > 1 && local.starts_with('"') && local.ends_with('"') {
+        local[1..local.len() - 1]
+            .chars()
+            .all(|c| c != '"' && c != '\\' && !c.is_control())
+    } else {
+        !local.starts_with('.')
+            && !local.ends_with('.')
+            && !local.contains("..")
+            && local
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~.".contains(c))
+    };
+
+    let valid_domain = if domain.starts_with('[') && domain.ends_with(']') {
+        domain.len() > 2
+    } else {
+        domain.len() <= 255
+            && domain.split('.').all(|label| {
+                !label.is_empty()
+                    && label.len() <= 63
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+            })
+    };
+
+    valid_local && valid_domain
 }
 
 /// This is a representation of the data storage for the
 /// inline Merge feature from SocketLabs. More about it:
 /// [https://www.socketlabs.com/blog/unleash-power-merge-fields/].
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct MergeData<'a> {
     /// A vector used to define merge field data for each
     /// message. Variables can be freely named, with the
     /// exception of a single reserved word, `DeliveryAddress`
     /// which defines the recipient of the current message
-    per_message: Vec<Data<'a>>,
+    #[serde(default)]
+    per_message: Vec<Vec<Data<'a>>>,
     /// A vector used to define merge field data for all
     /// messages in the injection
+    #[serde(default)]
     global: Vec<Data<'a>>,
 }
 
 /// Helper struct to hold the `field/value` data for
 /// the SocketLabs inline Merge feature.
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct Data<'a> {
     field: Cow<'a, str>,
     value: Cow<'a, str>,
 }
 
+impl<'a> MergeData<'a> {
+    fn into_owned(self) -> MergeData<'static> {
+        MergeData {
+            per_message: self
+                .per_message
+                .into_iter()
+                .map(|row| row.into_iter().map(Data::into_owned).collect())
+                .collect(),
+            global: self.global.into_iter().map(Data::into_owned).collect(),
+        }
+    }
+}
+
+impl<'a> Data<'a> {
+    fn as_pair(&self) -> (&str, &str) {
+        (&self.field, &self.value)
+    }
+
+    fn into_owned(self) -> Data<'static> {
+        Data {
+            field: owned(self.field),
+            value: owned(self.value),
+        }
+    }
+}
+
+/// The priority of a message, as shown by mail clients.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    High,
+    Normal,
+    Low,
+}
+
 /// This is a representation of a valid
 /// SocketLabs email message.
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Message<'a> {
     /// A vector of recipients for this message.
+    #[serde(default)]
     to: Vec<Email<'a>>,
     /// The sender for this message.
     from: Email<'a>,
     /// The subject of this message.
+    #[serde(default)]
     subject: Cow<'a, str>,
     /// The text part of the message.
+    #[serde(default)]
     text_body: Cow<'a, str>,
     /// The optional html part of the message.
     #[serde(skip_serializing_if = "Option::is_none")]
     html_body: Option<Cow<'a, str>>,
+    /// The optional AMP for Email part of the message.
+    /// Clients without AMP support display the html part instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amp_body: Option<Cow<'a, str>>,
     /// The optional integer ID referencing content
     /// from the Email Content Manager in the
     /// SocketLabs Control Panel More about it:
@@ -137,23 +569,25 @@ pub struct Message<'a> {
     /// Data storage for the inline Merge feature.
     #[serde(skip_serializing_if = "Option::is_none")]
     merge_data: Option<MergeData<'a>>,
+    /// Injection API fields not modeled by this struct,
+    /// serialized along with the ones above.
+    #[serde(flatten)]
+    extra: HashMap<String, Value>,
 }
 
 impl<'a> Message<'a> {
     /// Create a new Message object with all fields empty
     /// but the `from` field.
     pub fn new<T: Into<Cow<'a, str>>>(address: T, name: Option<T>) -> Message<'a> {
-        let from = match name {
-            Some(name) => Email::new(address.into(), Some(name.into())),
-            None => Email::new(address.into(), None),
-        };
+        let from = Email::mailbox(address, name);
 
         Message {
             to: Vec::new(),
-            from: from,
+            from,
             subject: "".into(),
             text_body: "".into(),
             html_body: None,
+            amp_body: None,
             api_template: None,
             mailing_id: None,
             message_id: None,
@@ -162,27 +596,120 @@ impl<'a> Message<'a> {
             cc: None,
             bcc: None,
             reply_to: None,
-            // TODO: create add_attachment function
             attachment: None,
-            // TODO: create add_merge_data function
             merge_data: None,
+            extra: HashMap::new(),
         }
     }
 
+    /// Create a new Message object sending the SocketLabs
+    /// api template with id `template_id`, from the given sender.
+    ///
+    /// The template takes the place of the bodies, setting any
+    /// of them afterwards makes `Message::validate` fail with
+    /// `ValidationError::MessageBodyConflict`.
+    pub fn with_template<T: Into<Cow<'a, str>>>(
+        template_id: u64,
+        address: T,
+        name: Option<T>,
+    ) -> Message<'a> {
+        let mut message = Message::new(address, name);
+        message.set_template(template_id);
+        message
+    }
+
+    /// Expands `base` into one message per recipient, each
+    /// addressed to a single recipient and carrying its own
+    /// per-message merge fields.
+    ///
+    /// The reserved `DeliveryAddress` merge field is filled with
+    /// the recipient address. Everything else, including global
+    /// merge fields, is copied from `base`.
+    pub fn bulk<I, F, K, V>(base: &Message<'a>, recipients: I) -> Vec<Message<'a>>
+    where
+        I: IntoIterator<Item = (Email<'a>, F)>,
+        F: IntoIterator<Item = (K, V)>,
+        K: Into<Cow<'a, str>>,
+        V: Into<Cow<'a, str>>,
+    {
+        recipients
+            .into_iter()
+            .map(|(email, fields)| {
+                let mut message = base.clone();
+                let mut data = vec![Data {
+                    field: DELIVERY_ADDRESS.into(),
+                    value: email.email_address.clone(),
+                }];
+                data.extend(fields.into_iter().map(|(field, value)| Data {
+                    field: field.into(),
+                    value: value.into(),
+                }));
+
+                message.to = vec![email];
+                message.merge_data_mut().per_message = vec![data];
+                message
+            })
+            .collect()
+    }
+
+    /// Copies the message for a single recipient.
+    ///
+    /// Everything is copied but the To, Cc and Bcc recipients,
+    /// which are replaced by `to`, and the message id, which
+    /// is left for each copy to set its own.
+    pub fn clone_for(&self, to: Email<'a>) -> Message<'a> {
+        let mut message = self.clone();
+        message.to = vec![to];
+        message.cc = None;
+        message.bcc = None;
+        message.message_id = None;
+        message
+    }
+
     /// Adds a new recipient to the Message struct.
     pub fn add_to<T: Into<Cow<'a, str>>>(&mut self, address: T, name: Option<T>) {
-        match name {
-            Some(name) => self.to.push(Email::new(address.into(), Some(name.into()))),
-            None => self.to.push(Email::new(address.into(), None)),
+        self.to.push(Email::mailbox(address, name))
+    }
+
+    /// Removes the recipients with the given address, compared case-insensitively.
+    pub fn remove_to(&mut self, address: &str) {
+        self.to
+            .retain(|email| !email.email_address.eq_ignore_ascii_case(address));
+    }
+
+    /// Removes all recipients.
+    pub fn clear_to(&mut self) {
+        self.to.clear()
+    }
+
+    /// Adds several recipients to the Message struct. Each one
+    /// is an address, or a `Display Name <local@domain>` mailbox.
+    pub fn add_to_many<T: Into<Cow<'a, str>>, I: IntoIterator<Item = T>>(&mut self, recipients: I) {
+        self.to.extend(
+            recipients
+                .into_iter()
+                .map(|mailbox| Email::mailbox(mailbox, None)),
+        );
+    }
+
+    /// Sets up this message for bulk injection to all `recipients`.
+    ///
+    /// The only To address becomes the `%%DeliveryAddress%%` placeholder
+    /// and the per-message merge rows are replaced by one holding
+    /// the `DeliveryAddress` field for each recipient.
+    pub fn set_bulk_to<T: Into<Cow<'a, str>>, I: IntoIterator<Item = T>>(&mut self, recipients: I) {
+        self.to = vec![Email::new("%%DeliveryAddress%%".into(), None)];
+        if let Some(ref mut merge_data) = self.merge_data {
+            merge_data.per_message.clear();
+        }
+        for address in recipients {
+            self.add_per_message_merge(vec![(DELIVERY_ADDRESS, address.into())]);
         }
     }
 
     /// Sets the from field in the Message struct.
     pub fn set_from<T: Into<Cow<'a, str>>>(&mut self, address: T, name: Option<T>) {
-        match name {
-            Some(name) => self.from = Email::new(address.into(), Some(name.into())),
-            None => self.from = Email::new(address.into(), None),
-        }
+        self.from = Email::mailbox(address, name)
     }
 
     /// Sets the subject field in the Message struct.
@@ -200,43 +727,177 @@ impl<'a> Message<'a> {
         self.html_body = Some(html.into())
     }
 
+    /// Sets the html_body field in the Message struct, and the
+    /// text_body field to a plain text rendering of the html.
+    #[cfg(feature = "html2text")]
+    pub fn set_html_with_fallback<T: Into<Cow<'a, str>>>(&mut self, html: T) -> Result<()> {
+        let html = html.into();
+        let text = html2text::from_read(html.as_bytes(), 80)
+            .map_err(|error| ErrorKind::InvalidBody(error.to_string()))?;
+
+        self.set_text(text);
+        self.set_html(html);
+        Ok(())
+    }
+
+    /// Sets the html_body field in the Message struct to the
+    /// rendered Markdown source, and the text_body field to the source.
+    #[cfg(feature = "markdown")]
+    pub fn set_markdown<T: Into<Cow<'a, str>>>(&mut self, markdown: T) {
+        let markdown = markdown.into();
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new(&markdown));
+
+        self.set_html(html);
+        self.set_text(markdown);
+    }
+
+    /// Sets the html_body field in the Message struct, inlining the rules
+    /// of the `css` stylesheet. See `html::inline_css`.
+    #[cfg(feature = "inline-css")]
+    pub fn set_html_inlined(&mut self, html: &str, css: &str) {
+        self.set_html(crate::html::inline_css(html, css))
+    }
+
+    /// Sets the amp_body field in the Message struct.
+    pub fn set_amp<T: Into<Cow<'a, str>>>(&mut self, amp: T) {
+        self.amp_body = Some(amp.into())
+    }
+
     /// Sets the api_template field in the Message struct.
     pub fn set_api_template<T: Into<Cow<'a, str>>>(&mut self, api_template: T) {
         self.api_template = Some(api_template.into())
     }
 
+    /// Sets the api_template field in the Message struct from
+    /// a numeric template id, clearing any body already set
+    /// since SocketLabs rejects messages with both.
+    pub fn set_template(&mut self, template_id: u64) {
+        self.text_body = "".into();
+        self.html_body = None;
+        self.amp_body = None;
+        self.api_template = Some(template_id.to_string().into())
+    }
+
     /// Sets the message_id field in the Message struct.
     pub fn set_message_id<T: Into<Cow<'a, str>>>(&mut self, message_id: T) {
         self.message_id = Some(message_id.into())
     }
 
+    /// Sets the message_id field in the Message struct to a
+    /// random UUID, unless it is already set, and returns it.
+    ///
+    /// The id can be stored to correlate the message with
+    /// SocketLabs webhook events and reports later.
+    pub fn generate_message_id(&mut self) -> &str {
+        self.message_id
+            .get_or_insert_with(|| Uuid::new_v4().to_string().into())
+    }
+
     /// Sets the charset field in the Message struct.
+    /// Accepts a name or an `encoding::Charset`.
     pub fn set_charset<T: Into<Cow<'a, str>>>(&mut self, charset: T) {
         self.charset = Some(charset.into())
     }
 
-    /// Adds headers to the custom_header field in the Message struct.
-    pub fn add_headers<T: Into<Cow<'a, str>> + Eq + Hash>(&mut self, headers: HashMap<T, T>) {
-        if self.custom_headers.is_none() {
-            self.custom_headers = Some(Vec::new());
+    /// Adds a header to the custom_header field in the Message struct.
+    /// Headers keep their insertion order and names may repeat.
+    pub fn add_header<K: Into<Cow<'a, str>>, V: Into<Cow<'a, str>>>(&mut self, name: K, value: V) {
+        let header = CustomHeader {
+            name: name.into(),
+            value: value.into(),
+        };
+
+        match self.custom_headers {
+            Some(ref mut headers) => headers.push(header),
+            None => self.custom_headers = Some(vec![header]),
         }
+    }
 
-        if let Some(ref mut custom_headers) = self.custom_headers {
-            for (name, value) in headers {
-                custom_headers.push(CustomHeader {
-                    name: name.into(),
-                    value: value.into(),
-                })
+    /// Adds headers to the custom_header field in the Message struct,
+    /// in iteration order. See `Message::add_header`.
+    pub fn add_headers<K, V, I>(&mut self, headers: I)
+    where
+        K: Into<Cow<'a, str>>,
+        V: Into<Cow<'a, str>>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        for (name, value) in headers {
+            self.add_header(name, value);
+        }
+    }
+
+    /// Sets the `List-Unsubscribe` header, and the `List-Unsubscribe-Post`
+    /// header for one-click unsubscribe (RFC 8058) when a `url` is given.
+    ///
+    /// The `mailto` address may be given with or without the `mailto:`
+    /// scheme. Passing neither removes both headers.
+    pub fn set_unsubscribe<T: AsRef<str>>(&mut self, mailto: Option<T>, url: Option<T>) {
+        let mut targets = Vec::new();
+        if let Some(mailto) = mailto {
+            let mailto = mailto.as_ref().trim();
+            if mailto.starts_with("mailto:") {
+                targets.push(format!("<{}>", mailto));
+            } else {
+                targets.push(format!("<mailto:{}>", mailto));
             }
         }
+        if let Some(ref url) = url {
+            targets.push(format!("<{}>", url.as_ref().trim()));
+        }
+
+        if targets.is_empty() {
+            self.remove_header("List-Unsubscribe");
+        } else {
+            self.set_header("List-Unsubscribe", targets.join(", "));
+        }
+
+        if url.is_some() {
+            self.set_header(
+                "List-Unsubscribe-Post",
+                "List-Unsubscribe=One-Click".to_string(),
+            );
+        } else {
+            self.remove_header("List-Unsubscribe-Post");
+        }
+    }
+
+    /// Sets the `X-Priority`, `Importance` and `X-MSMail-Priority`
+    /// headers consistently, replacing any previous values.
+    pub fn set_priority(&mut self, priority: Priority) {
+        let (x_priority, importance, ms_mail) = match priority {
+            Priority::High => ("1 (Highest)", "high", "High"),
+            Priority::Normal => ("3 (Normal)", "normal", "Normal"),
+            Priority::Low => ("5 (Lowest)", "low", "Low"),
+        };
+
+        self.set_header("X-Priority", x_priority.to_string());
+        self.set_header("Importance", importance.to_string());
+        self.set_header("X-MSMail-Priority", ms_mail.to_string());
+    }
+
+    /// Sets a custom header, replacing any headers with the
+    /// same name, compared case-insensitively.
+    fn set_header(&mut self, name: &'static str, value: String) {
+        self.remove_header(name);
+        self.add_header(name, value);
+    }
+
+    /// Removes the custom headers with the given name, compared case-insensitively.
+    pub fn remove_header(&mut self, name: &str) {
+        if let Some(ref mut headers) = self.custom_headers {
+            headers.retain(|header| !header.name.eq_ignore_ascii_case(name));
+        }
+    }
+
+    /// Removes all custom headers.
+    pub fn clear_headers(&mut self) {
+        self.custom_headers = None
     }
 
     /// Adds a new cc'd recipient to the Message struct.
     pub fn add_cc<T: Into<Cow<'a, str>>>(&mut self, address: T, name: Option<T>) {
-        let email = match name {
-            Some(name) => Email::new(address.into(), Some(name.into())),
-            None => Email::new(address.into(), None),
-        };
+        let email = Email::mailbox(address, name);
 
         match self.cc {
             Some(ref mut cc) => cc.push(email),
@@ -244,12 +905,31 @@ impl<'a> Message<'a> {
         }
     }
 
+    /// Removes the cc'd recipients with the given address, compared case-insensitively.
+    pub fn remove_cc(&mut self, address: &str) {
+        if let Some(ref mut cc) = self.cc {
+            cc.retain(|email| !email.email_address.eq_ignore_ascii_case(address));
+        }
+    }
+
+    /// Removes all cc'd recipients.
+    pub fn clear_cc(&mut self) {
+        self.cc = None
+    }
+
+    /// Adds several cc'd recipients to the Message struct.
+    /// See `Message::add_to_many`.
+    pub fn add_cc_many<T: Into<Cow<'a, str>>, I: IntoIterator<Item = T>>(&mut self, recipients: I) {
+        self.cc.get_or_insert_with(Vec::new).extend(
+            recipients
+                .into_iter()
+                .map(|mailbox| Email::mailbox(mailbox, None)),
+        );
+    }
+
     /// Adds a new bcc'd recipient to the Message struct.
     pub fn add_bcc<T: Into<Cow<'a, str>>>(&mut self, address: T, name: Option<T>) {
-        let email = match name {
-            Some(name) => Email::new(address.into(), Some(name.into())),
-            None => Email::new(address.into(), None),
-        };
+        let email = Email::mailbox(address, name);
 
         match self.bcc {
             Some(ref mut bcc) => bcc.push(email),
@@ -257,11 +937,718 @@ impl<'a> Message<'a> {
         }
     }
 
+    /// Removes the bcc'd recipients with the given address, compared case-insensitively.
+    pub fn remove_bcc(&mut self, address: &str) {
+        if let Some(ref mut bcc) = self.bcc {
+            bcc.retain(|email| !email.email_address.eq_ignore_ascii_case(address));
+        }
+    }
+
+    /// Removes all bcc'd recipients.
+    pub fn clear_bcc(&mut self) {
+        self.bcc = None
+    }
+
+    /// Adds several bcc'd recipients to the Message struct.
+    /// See `Message::add_to_many`.
+    pub fn add_bcc_many<T: Into<Cow<'a, str>>, I: IntoIterator<Item = T>>(
+        &mut self,
+        recipients: I,
+    ) {
+        self.bcc.get_or_insert_with(Vec::new).extend(
+            recipients
+                .into_iter()
+                .map(|mailbox| Email::mailbox(mailbox, None)),
+        );
+    }
+
+    /// The number of recipients across the To, Cc and Bcc lists.
+    pub fn recipient_count(&self) -> usize {
+        self.to.len() + self.cc.as_ref().map_or(0, Vec::len) + self.bcc.as_ref().map_or(0, Vec::len)
+    }
+
+    /// The size in bytes of this message once serialized for
+    /// the Injection API, including base64 encoded attachments.
+    pub fn estimated_size(&self) -> usize {
+        let mut counter = ByteCounter(0);
+        // Writing to the counter never fails, neither does serializing a message.
+        let _ = serde_json::to_writer(&mut counter, self);
+        counter.0
+    }
+
+    /// Removes duplicated recipients across the To, Cc and Bcc
+    /// lists, comparing addresses case-insensitively. The first
+    /// occurrence is kept, looking at To, then Cc, then Bcc.
+    pub fn dedupe_recipients(&mut self) {
+        let mut seen = HashSet::new();
+        let mut first = |email: &Email| seen.insert(email.email_address.to_lowercase());
+
+        self.to.retain(&mut first);
+        if let Some(ref mut cc) = self.cc {
+            cc.retain(&mut first);
+        }
+        if let Some(ref mut bcc) = self.bcc {
+            bcc.retain(&mut first);
+        }
+    }
+
     /// Sets the from field in the Message struct.
     pub fn set_reply_to<T: Into<Cow<'a, str>>>(&mut self, address: T, name: Option<T>) {
-        match name {
-            Some(name) => self.reply_to = Some(Email::new(address.into(), Some(name.into()))),
-            None => self.reply_to = Some(Email::new(address.into(), None)),
+        self.reply_to = Some(Email::mailbox(address, name))
+    }
+
+    /// Adds a new attachment to the Message struct.
+    pub fn add_attachment<N: Into<Cow<'a, str>>, T: Into<Cow<'a, str>>, B: Into<Vec<u8>>>(
+        &mut self,
+        name: N,
+        content_type: T,
+        content: B,
+    ) {
+        self.attach(Attachment::new(name, content_type, content))
+    }
+
+    /// Reads the file at `path` and adds it as an attachment
+    /// to the Message struct. See `Attachment::from_path`.
+    pub fn add_attachment_from_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.attach(Attachment::from_path(path)?);
+        Ok(())
+    }
+
+    /// Adds an image to be displayed inline in the html body.
+    ///
+    /// Returns the generated content id, which can be
+    /// referenced from the html body as `cid:<content_id>`.
+    pub fn add_inline_image<N: Into<Cow<'a, str>>, T: Into<Cow<'a, str>>, B: Into<Vec<u8>>>(
+        &mut self,
+        name: N,
+        content_type: T,
+        content: B,
+    ) -> String {
+        self.attach_inline(Attachment::new(name, content_type, content))
+    }
+
+    /// Reads the image at `path` and adds it to be displayed inline
+    /// in the html body. See `Message::add_inline_image`.
+    pub fn add_inline_image_from_path<P: AsRef<Path>>(&mut self, path: P) -> Result<String> {
+        Ok(self.attach_inline(Attachment::from_path(path)?))
+    }
+
+    /// Gives the attachment a new content id and adds it to the message.
+    fn attach_inline(&mut self, mut attachment: Attachment<'a>) -> String {
+        let content_id = Uuid::new_v4().simple().to_string();
+        attachment.set_content_id(content_id.clone());
+        self.attach(attachment);
+        content_id
+    }
+
+    /// Attaches an iCalendar invite, so that mail clients
+    /// display it as an event which can be accepted or declined.
+    ///
+    /// The iTIP method, such as `REQUEST` or `CANCEL`, is taken
+    /// from the `METHOD` property of `ics`, defaulting to `REQUEST`.
+    pub fn attach_calendar_invite(&mut self, ics: &str) {
+        let method = ics
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("METHOD:"))
+            .map_or("REQUEST", str::trim);
+
+        let mut attachment = Attachment::new(
+            "invite.ics".to_string(),
+            format!("text/calendar; method={}; charset=UTF-8", method),
+            ics,
+        );
+        attachment.add_header("Content-Disposition", "inline; filename=\"invite.ics\"");
+        self.attach(attachment);
+    }
+
+    /// Attaches a vCard, so that mail clients offer
+    /// to add the contact it describes to the address book.
+    pub fn attach_vcard<T: Into<Cow<'a, str>>>(&mut self, vcard: &str, filename: T) {
+        let filename = filename.into();
+        let disposition = format!("attachment; filename=\"{}\"", filename);
+
+        let mut attachment =
+            Attachment::new(filename, Cow::Borrowed("text/vcard; charset=UTF-8"), vcard);
+        attachment.add_header("Content-Disposition", disposition);
+        self.attach(attachment);
+    }
+
+    /// Adds an already built attachment to the Message struct.
+    pub fn attach(&mut self, attachment: Attachment<'a>) {
+        match self.attachment {
+            Some(ref mut attachments) => attachments.push(attachment),
+            None => self.attachment = Some(vec![attachment]),
+        }
+    }
+
+    /// Removes the attachments with the given name.
+    pub fn remove_attachment(&mut self, name: &str) {
+        if let Some(ref mut attachments) = self.attachment {
+            attachments.retain(|attachment| attachment.name != name);
+        }
+    }
+
+    /// Removes all attachments.
+    pub fn clear_attachments(&mut self) {
+        self.attachment = None
+    }
+
+    /// Gets the attachments added to the Message struct.
+    pub fn attachments(&self) -> &[Attachment<'a>] {
+        self.attachment.as_deref().unwrap_or(&[])
+    }
+
+    /// Adds the merge field data for one message of the inline Merge.
+    ///
+    /// Each call defines the fields for a single recipient, which
+    /// is set through the reserved `DeliveryAddress` field.
+    pub fn add_per_message_merge<K, V, I>(&mut self, fields: I)
+    where
+        K: Into<Cow<'a, str>>,
+        V: Into<Cow<'a, str>>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let data = fields
+            .into_iter()
+            .map(|(field, value)| Data {
+                field: field.into(),
+                value: value.into(),
+            })
+            .collect();
+        self.merge_data_mut().per_message.push(data);
+    }
+
+    /// Sets a merge field shared by all messages of the inline Merge,
+    /// replacing any previous value for the same field.
+    pub fn set_global_merge<K: Into<Cow<'a, str>>, V: Into<Cow<'a, str>>>(
+        &mut self,
+        field: K,
+        value: V,
+    ) {
+        let field = field.into();
+        let value = value.into();
+        let global = &mut self.merge_data_mut().global;
+
+        match global.iter_mut().find(|data| data.field == field) {
+            Some(data) => data.value = value,
+            None => global.push(Data { field, value }),
+        }
+    }
+
+    /// Sets several merge fields shared by all messages of the inline Merge.
+    /// See `Message::set_global_merge`.
+    pub fn add_global_merge<K, V, I>(&mut self, fields: I)
+    where
+        K: Into<Cow<'a, str>>,
+        V: Into<Cow<'a, str>>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        for (field, value) in fields {
+            self.set_global_merge(field, value);
         }
     }
+
+    /// Adds the merge field data for one message of the inline Merge
+    /// from any struct or map, using its field names as merge fields.
+    /// See `Message::add_per_message_merge`.
+    pub fn add_per_message_merge_struct<T: Serialize>(&mut self, fields: &T) -> Result<()> {
+        self.add_per_message_merge(merge_fields(fields)?);
+        Ok(())
+    }
+
+    /// Sets merge fields shared by all messages of the inline Merge
+    /// from any struct or map, using its field names as merge fields.
+    /// See `Message::set_global_merge`.
+    pub fn add_global_merge_struct<T: Serialize>(&mut self, fields: &T) -> Result<()> {
+        self.add_global_merge(merge_fields(fields)?);
+        Ok(())
+    }
+
+    /// Encodes the subject and the friendly names of every address
+    /// containing non-ASCII characters as RFC 2047 encoded-words.
+    /// See `encoding::encode_word`.
+    pub fn encode_headers(&mut self) {
+        if let Cow::Owned(subject) = encode_word(&self.subject) {
+            self.subject = subject.into();
+        }
+
+        let emails = self
+            .to
+            .iter_mut()
+            .chain(self.cc.iter_mut().flatten())
+            .chain(self.bcc.iter_mut().flatten())
+            .chain(self.reply_to.iter_mut())
+            .chain(Some(&mut self.from));
+        for email in emails {
+            if let Some(ref mut name) = email.friendly_name {
+                if let Cow::Owned(encoded) = encode_word(name) {
+                    *name = encoded.into();
+                }
+            }
+        }
+    }
+
+    /// Checks that this message doesn't set both a body and an
+    /// api template, which makes SocketLabs reject the whole
+    /// injection. Also part of `Message::validate`.
+    pub fn check_body_conflict(&self) -> std::result::Result<(), ValidationError> {
+        let has_body =
+            !self.text_body.is_empty() || self.html_body.is_some() || self.amp_body.is_some();
+        if self.api_template.is_some() && has_body {
+            return Err(ValidationError::MessageBodyConflict);
+        }
+
+        Ok(())
+    }
+
+    /// The total size, in bytes, of the base64 encoded attachments.
+    pub fn attachments_size(&self) -> usize {
+        self.attachments()
+            .iter()
+            .map(|attachment| base64_len(&attachment.content))
+            .sum()
+    }
+
+    /// Checks that the attachments of this message add up to at
+    /// most `MAX_ATTACHMENTS_SIZE`, naming the first attachment
+    /// going over it otherwise. Also part of `Message::validate`.
+    pub fn check_attachments_size(&self) -> std::result::Result<(), ValidationError> {
+        let mut total = 0;
+        for attachment in self.attachments() {
+            total += base64_len(&attachment.content);
+            if total > MAX_ATTACHMENTS_SIZE {
+                return Err(ValidationError::AttachmentsTooLarge(
+                    attachment.name.to_string(),
+                    total,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks this message for problems that would make
+    /// SocketLabs reject it, without making any request.
+    ///
+    /// All problems found are returned, not only the first one.
+    pub fn validate(&self) -> std::result::Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+
+        if !is_valid_address(self.from.email_address.trim()) {
+            errors.push(ValidationError::InvalidFromAddress);
+        }
+
+        if self.to.is_empty() {
+            errors.push(ValidationError::EmptyToAddress);
+        }
+
+        let recipients = self
+            .to
+            .iter()
+            .chain(self.cc.iter().flatten())
+            .chain(self.bcc.iter().flatten());
+        for recipient in recipients {
+            let address = recipient.email_address.trim();
+            if !is_merge_field(address) && !is_valid_address(address) {
+                errors.push(ValidationError::InvalidRecipientAddress(
+                    recipient.email_address.to_string(),
+                ));
+            }
+        }
+
+        let recipients = self.recipient_count();
+        if recipients > MAX_RECIPIENTS_PER_MESSAGE {
+            errors.push(ValidationError::TooManyRecipients(recipients));
+        }
+
+        if self.api_template.is_none() && self.subject.trim().is_empty() {
+            errors.push(ValidationError::EmptySubject);
+        }
+
+        if let Err(error) = self.check_body_conflict() {
+            errors.push(error);
+        } else if self.api_template.is_none()
+            && self.text_body.is_empty()
+            && self.html_body.is_none()
+        {
+            errors.push(ValidationError::NoValidBodyParts);
+        }
+
+        if let Err(error) = self.check_attachments_size() {
+            errors.push(error);
+        }
+
+        if let Some(ref merge_data) = self.merge_data {
+            if merge_data
+                .global
+                .iter()
+                .any(|data| data.field == DELIVERY_ADDRESS)
+            {
+                errors.push(ValidationError::ReservedMergeField);
+            }
+
+            for (index, row) in merge_data.per_message.iter().enumerate() {
+                if !row.iter().any(|data| data.field == DELIVERY_ADDRESS) {
+                    errors.push(ValidationError::MissingDeliveryAddress(index));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Removes all merge data, both per-message and global.
+    pub fn clear_merge_data(&mut self) {
+        self.merge_data = None
+    }
+
+    /// Sets an Injection API field which this crate doesn't
+    /// support yet. It is sent as is, along with the other fields.
+    pub fn set_extra<K: Into<String>, V: Into<Value>>(&mut self, field: K, value: V) {
+        self.extra.insert(field.into(), value.into());
+    }
+
+    /// Removes a field set through `Message::set_extra`.
+    pub fn remove_extra(&mut self, field: &str) {
+        self.extra.remove(field);
+    }
+
+    /// Gets the merge data, creating it if it is not set yet.
+    fn merge_data_mut(&mut self) -> &mut MergeData<'a> {
+        self.merge_data.get_or_insert_with(|| MergeData {
+            per_message: Vec::new(),
+            global: Vec::new(),
+        })
+    }
+}
+
+/// Chainable versions of the `Message` setters, so a
+/// message can be built in a single expression. See also
+/// `builder::MessageBuilder`, which checks required fields.
+impl<'a> Message<'a> {
+    /// Adds a new recipient. See `Message::add_to`.
+    pub fn with_to<T: Into<Cow<'a, str>>>(mut self, address: T, name: Option<T>) -> Self {
+        self.add_to(address, name);
+        self
+    }
+
+    /// Sets the sender. See `Message::set_from`.
+    pub fn with_from<T: Into<Cow<'a, str>>>(mut self, address: T, name: Option<T>) -> Self {
+        self.set_from(address, name);
+        self
+    }
+
+    /// Sets the subject. See `Message::set_subject`.
+    pub fn with_subject<T: Into<Cow<'a, str>>>(mut self, subject: T) -> Self {
+        self.set_subject(subject);
+        self
+    }
+
+    /// Sets the text body. See `Message::set_text`.
+    pub fn with_text<T: Into<Cow<'a, str>>>(mut self, text: T) -> Self {
+        self.set_text(text);
+        self
+    }
+
+    /// Sets the html body. See `Message::set_html`.
+    pub fn with_html<T: Into<Cow<'a, str>>>(mut self, html: T) -> Self {
+        self.set_html(html);
+        self
+    }
+
+    /// Sets the AMP body. See `Message::set_amp`.
+    pub fn with_amp<T: Into<Cow<'a, str>>>(mut self, amp: T) -> Self {
+        self.set_amp(amp);
+        self
+    }
+
+    /// Sets the api template. See `Message::set_api_template`.
+    pub fn with_api_template<T: Into<Cow<'a, str>>>(mut self, api_template: T) -> Self {
+        self.set_api_template(api_template);
+        self
+    }
+
+    /// Sets the message id. See `Message::set_message_id`.
+    pub fn with_message_id<T: Into<Cow<'a, str>>>(mut self, message_id: T) -> Self {
+        self.set_message_id(message_id);
+        self
+    }
+
+    /// Sets a random message id, unless one is already
+    /// set. See `Message::generate_message_id`.
+    pub fn with_generated_id(mut self) -> Self {
+        self.generate_message_id();
+        self
+    }
+
+    /// Sets the charset. See `Message::set_charset`.
+    pub fn with_charset<T: Into<Cow<'a, str>>>(mut self, charset: T) -> Self {
+        self.set_charset(charset);
+        self
+    }
+
+    /// Sets the priority. See `Message::set_priority`.
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.set_priority(priority);
+        self
+    }
+
+    /// Adds a custom header. See `Message::add_header`.
+    pub fn with_header<K: Into<Cow<'a, str>>, V: Into<Cow<'a, str>>>(
+        mut self,
+        name: K,
+        value: V,
+    ) -> Self {
+        self.add_header(name, value);
+        self
+    }
+
+    /// Adds custom headers. See `Message::add_headers`.
+    pub fn with_headers<K, V, I>(mut self, headers: I) -> Self
+    where
+        K: Into<Cow<'a, str>>,
+        V: Into<Cow<'a, str>>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.add_headers(headers);
+        self
+    }
+
+    /// Adds a new cc'd recipient. See `Message::add_cc`.
+    pub fn with_cc<T: Into<Cow<'a, str>>>(mut self, address: T, name: Option<T>) -> Self {
+        self.add_cc(address, name);
+        self
+    }
+
+    /// Adds a new bcc'd recipient. See `Message::add_bcc`.
+    pub fn with_bcc<T: Into<Cow<'a, str>>>(mut self, address: T, name: Option<T>) -> Self {
+        self.add_bcc(address, name);
+        self
+    }
+
+    /// Sets the reply to address. See `Message::set_reply_to`.
+    pub fn with_reply_to<T: Into<Cow<'a, str>>>(mut self, address: T, name: Option<T>) -> Self {
+        self.set_reply_to(address, name);
+        self
+    }
+
+    /// Adds a new attachment. See `Message::add_attachment`.
+    pub fn with_attachment<N: Into<Cow<'a, str>>, T: Into<Cow<'a, str>>, B: Into<Vec<u8>>>(
+        mut self,
+        name: N,
+        content_type: T,
+        content: B,
+    ) -> Self {
+        self.add_attachment(name, content_type, content);
+        self
+    }
+
+    /// Adds merge data for one message. See `Message::add_per_message_merge`.
+    pub fn with_per_message_merge<K, V, I>(mut self, fields: I) -> Self
+    where
+        K: Into<Cow<'a, str>>,
+        V: Into<Cow<'a, str>>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.add_per_message_merge(fields);
+        self
+    }
+
+    /// Sets a global merge field. See `Message::set_global_merge`.
+    pub fn with_global_merge<K: Into<Cow<'a, str>>, V: Into<Cow<'a, str>>>(
+        mut self,
+        field: K,
+        value: V,
+    ) -> Self {
+        self.set_global_merge(field, value);
+        self
+    }
+
+    /// Sets an unmodeled field. See `Message::set_extra`.
+    pub fn with_extra<K: Into<String>, V: Into<Value>>(mut self, field: K, value: V) -> Self {
+        self.set_extra(field, value);
+        self
+    }
+}
+
+/// Read-only access to the `Message` fields, so a
+/// message can be inspected before it is sent.
+impl<'a> Message<'a> {
+    /// The recipients of this message.
+    pub fn to(&self) -> &[Email<'a>] {
+        &self.to
+    }
+
+    /// The sender of this message.
+    pub fn from(&self) -> &Email<'a> {
+        &self.from
+    }
+
+    /// The subject of this message.
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// The text part of this message.
+    pub fn text_body(&self) -> &str {
+        &self.text_body
+    }
+
+    /// The html part of this message, if any.
+    pub fn html_body(&self) -> Option<&str> {
+        self.html_body.as_deref()
+    }
+
+    /// The AMP part of this message, if any.
+    pub fn amp_body(&self) -> Option<&str> {
+        self.amp_body.as_deref()
+    }
+
+    /// The API Template of this message, if any.
+    pub fn api_template(&self) -> Option<&str> {
+        self.api_template.as_deref()
+    }
+
+    /// The mailing id of this message, if any.
+    pub fn mailing_id(&self) -> Option<&str> {
+        self.mailing_id.as_deref()
+    }
+
+    /// The message id of this message, if any.
+    pub fn message_id(&self) -> Option<&str> {
+        self.message_id.as_deref()
+    }
+
+    /// The charset of this message, if any.
+    pub fn charset(&self) -> Option<&str> {
+        self.charset.as_deref()
+    }
+
+    /// The custom headers of this message.
+    pub fn custom_headers(&self) -> &[CustomHeader<'a>] {
+        self.custom_headers.as_deref().unwrap_or(&[])
+    }
+
+    /// The cc'd recipients of this message.
+    pub fn cc(&self) -> &[Email<'a>] {
+        self.cc.as_deref().unwrap_or(&[])
+    }
+
+    /// The bcc'd recipients of this message.
+    pub fn bcc(&self) -> &[Email<'a>] {
+        self.bcc.as_deref().unwrap_or(&[])
+    }
+
+    /// The reply to address of this message, if any.
+    pub fn reply_to(&self) -> Option<&Email<'a>> {
+        self.reply_to.as_ref()
+    }
+
+    /// The per-message merge data, as `field/value` pairs for each message.
+    pub fn per_message_merge(&self) -> Vec<Vec<(&str, &str)>> {
+        self.merge_data
+            .as_ref()
+            .map_or_else(Vec::new, |merge_data| {
+                merge_data
+                    .per_message
+                    .iter()
+                    .map(|row| row.iter().map(Data::as_pair).collect())
+                    .collect()
+            })
+    }
+
+    /// The merge data shared by all messages, as `field/value` pairs.
+    pub fn global_merge(&self) -> Vec<(&str, &str)> {
+        self.merge_data
+            .as_ref()
+            .map_or_else(Vec::new, |merge_data| {
+                merge_data.global.iter().map(Data::as_pair).collect()
+            })
+    }
+
+    /// The Injection API fields not modeled by this struct.
+    pub fn extra(&self) -> &HashMap<String, Value> {
+        &self.extra
+    }
+
+    /// Converts into a message which owns all of its data,
+    /// copying whatever is still borrowed.
+    pub fn into_owned(self) -> OwnedMessage {
+        let emails = |emails: Vec<Email<'a>>| emails.into_iter().map(Email::into_owned).collect();
+
+        Message {
+            to: emails(self.to),
+            from: self.from.into_owned(),
+            subject: owned(self.subject),
+            text_body: owned(self.text_body),
+            html_body: self.html_body.map(owned),
+            amp_body: self.amp_body.map(owned),
+            api_template: self.api_template.map(owned),
+            mailing_id: self.mailing_id.map(owned),
+            message_id: self.message_id.map(owned),
+            charset: self.charset.map(owned),
+            custom_headers: self
+                .custom_headers
+                .map(|headers| headers.into_iter().map(CustomHeader::into_owned).collect()),
+            cc: self.cc.map(emails),
+            bcc: self.bcc.map(emails),
+            reply_to: self.reply_to.map(Email::into_owned),
+            attachment: self.attachment.map(|attachments| {
+                attachments
+                    .into_iter()
+                    .map(Attachment::into_owned)
+                    .collect()
+            }),
+            merge_data: self.merge_data.map(MergeData::into_owned),
+            extra: self.extra,
+        }
+    }
+}
+
+/// Takes ownership of a possibly borrowed string.
+fn owned(value: Cow<str>) -> Cow<'static, str> {
+    Cow::Owned(value.into_owned())
+}
+
+/// A writer which only counts the bytes written to it.
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Flattens a serializable struct or map into `field/value` pairs,
+/// in the order they are serialized in.
+///
+/// Strings are used as is, `null` becomes an empty string and
+/// any other value is rendered as JSON.
+fn merge_fields<T: Serialize>(fields: &T) -> Result<Vec<(String, String)>> {
+    match serde_json::to_value(fields)? {
+        Value::Object(map) => Ok(map
+            .into_iter()
+            .map(|(field, value)| {
+                let value = match value {
+                    Value::String(value) => value,
+                    Value::Null => String::new(),
+                    value => value.to_string(),
+                };
+                (field, value)
+            })
+            .collect()),
+        _ => Err(ErrorKind::InvalidMergeData(
+            "merge data must serialize to a struct or map".to_string(),
+        )
+        .into()),
+    }
 }
@@ -0,0 +1,130 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A registry of clients, one per tenant, for applications
+//! sending on behalf of several SocketLabs servers.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::client::SocketLabsClient;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::error::{ErrorKind, Result};
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::message::Message;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::response::Response;
+#[cfg(feature = "reqwest")]
+use crate::transport::ReqwestTransport;
+use crate::transport::Transport;
+#[cfg(not(feature = "reqwest"))]
+use crate::transport::UreqTransport;
+
+/// Clients keyed by tenant name, each with the credentials
+/// of its own SocketLabs server.
+///
+/// Tenants added with `MultiClient::add_tenant` share a single
+/// transport, so they send over the same pooled connections.
+///
+/// ```
+/// use socketlabs::multi::MultiClient;
+///
+/// let mut clients = MultiClient::new();
+/// clients.add_tenant("acme", 1234, "acme-api-key");
+/// clients.add_tenant("globex", 5678, "globex-api-key");
+/// assert!(clients.client("acme").is_some());
+/// ```
+#[derive(Debug)]
+pub struct MultiClient {
+    clients: HashMap<String, SocketLabsClient>,
+    /// The transport shared by the tenants added with their credentials.
+    transport: Arc<dyn Transport>,
+}
+
+impl MultiClient {
+    /// Creates a new registry, without any tenant.
+    pub fn new() -> MultiClient {
+        #[cfg(feature = "reqwest")]
+        let transport = ReqwestTransport::new();
+        #[cfg(not(feature = "reqwest"))]
+        let transport = UreqTransport::new();
+
+        MultiClient::with_transport(transport)
+    }
+
+    /// Creates a new registry, without any tenant, whose
+    /// tenants share `transport`.
+    pub fn with_transport<T: Transport + 'static>(transport: T) -> MultiClient {
+        MultiClient {
+            clients: HashMap::new(),
+            transport: Arc::new(transport),
+        }
+    }
+
+    /// Adds a tenant sending to the server `server_id` with
+    /// `api_key`, replacing any tenant of the same name.
+    pub fn add_tenant<T: Into<String>, K: Into<String>>(
+        &mut self,
+        tenant: T,
+        server_id: u16,
+        api_key: K,
+    ) {
+        let mut client = SocketLabsClient::new(server_id, api_key);
+        client.set_transport(Arc::clone(&self.transport));
+        self.clients.insert(tenant.into(), client);
+    }
+
+    /// Adds a tenant sending with `client`, such as one with its own
+    /// retries or rate limiter, replacing any tenant of the same name.
+    pub fn insert<T: Into<String>>(&mut self, tenant: T, client: SocketLabsClient) {
+        self.clients.insert(tenant.into(), client);
+    }
+
+    /// Removes a tenant, returning its client.
+    pub fn remove(&mut self, tenant: &str) -> Option<SocketLabsClient> {
+        self.clients.remove(tenant)
+    }
+
+    /// The client of a tenant.
+    pub fn client(&self, tenant: &str) -> Option<&SocketLabsClient> {
+        self.clients.get(tenant)
+    }
+
+    /// The names of every tenant, in no particular order.
+    pub fn tenants(&self) -> impl Iterator<Item = &str> {
+        self.clients.keys().map(String::as_str)
+    }
+
+    /// Sends the given messages with the client of `tenant`,
+    /// failing with `ErrorKind::UnknownTenant` if there is none.
+    /// See `SocketLabsClient::send`.
+    #[cfg(feature = "blocking")]
+    pub fn send_as(&self, tenant: &str, messages: &[Message]) -> Result<Response<'static>> {
+        self.tenant(tenant)?.send(messages)
+    }
+
+    /// Sends the given messages with the client of `tenant` without
+    /// blocking the current thread. See `MultiClient::send_as`.
+    #[cfg(feature = "async")]
+    pub async fn send_as_async(
+        &self,
+        tenant: &str,
+        messages: &[Message<'_>],
+    ) -> Result<Response<'static>> {
+        self.tenant(tenant)?.send_async(messages).await
+    }
+
+    /// The client of a tenant, which must exist.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn tenant(&self, tenant: &str) -> Result<&SocketLabsClient> {
+        self.client(tenant)
+            .ok_or_else(|| ErrorKind::UnknownTenant(tenant.to_string()).into())
+    }
+}
+
+impl Default for MultiClient {
+    fn default() -> MultiClient {
+        MultiClient::new()
+    }
+}
@@ -0,0 +1,150 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A queue of messages persisted on disk, so
+//! unsent messages survive process restarts.
+
+use std::path::Path;
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::client::SocketLabsClient;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::error::ErrorKind;
+use crate::error::Result;
+use crate::message::{Message, OwnedMessage};
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::request::MAX_MESSAGES_PER_REQUEST;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::response::{PostMessageErrorCode, Response};
+
+/// A queue of messages stored in a `sled` database.
+///
+/// Messages stay pending until the injection request they are
+/// sent in is accepted by SocketLabs, which gives at-least-once
+/// delivery: a crash right after sending sends them again.
+/// Messages SocketLabs rejects are removed along with the
+/// accepted ones, see `client::SocketLabsClient::set_dead_letter_sink`.
+#[derive(Clone, Debug)]
+pub struct PersistentQueue {
+    db: sled::Db,
+}
+
+impl PersistentQueue {
+    /// Opens the queue stored in the directory at `path`, creating
+    /// it if needed. Messages enqueued before are pending again.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<PersistentQueue> {
+        Ok(PersistentQueue {
+            db: sled::open(path)?,
+        })
+    }
+
+    /// Stores a message until it is sent, returning its id.
+    pub fn enqueue(&self, message: &Message) -> Result<u64> {
+        let id = self.db.generate_id()?;
+        self.db
+            .insert(id.to_be_bytes(), serde_json::to_vec(message)?)?;
+        self.db.flush()?;
+
+        Ok(id)
+    }
+
+    /// The pending messages along with their ids, in the order
+    /// they were enqueued.
+    pub fn pending(&self) -> Result<Vec<(u64, OwnedMessage)>> {
+        self.db
+            .iter()
+            .map(|entry| {
+                let (key, value) = entry?;
+                Ok((id(&key), serde_json::from_slice(&value)?))
+            })
+            .collect()
+    }
+
+    /// How many messages are pending.
+    pub fn len(&self) -> usize {
+        self.db.len()
+    }
+
+    /// Whether no message is pending.
+    pub fn is_empty(&self) -> bool {
+        self.db.is_empty()
+    }
+
+    /// Removes a pending message, such as one sent through other means.
+    pub fn remove(&self, id: u64) -> Result<()> {
+        self.db.remove(id.to_be_bytes())?;
+        self.db.flush()?;
+
+        Ok(())
+    }
+
+    /// Sends every pending message with `client`, removing them as
+    /// soon as the injection request they are part of is accepted.
+    ///
+    /// Requests which fail as a whole keep their messages pending, the
+    /// following ones are still sent. When only some fail, draining
+    /// fails with `ErrorKind::PartiallySent`, see `SocketLabsClient::send`.
+    ///
+    /// This blocks the current thread and must not be
+    /// called from within an async runtime, use `drain_async` there.
+    #[cfg(all(feature = "blocking", any(feature = "reqwest", feature = "ureq")))]
+    pub fn drain(&self, client: &SocketLabsClient) -> Result<Response<'static>> {
+        let mut results = Vec::new();
+        for (chunk, pending) in self.pending()?.chunks(MAX_MESSAGES_PER_REQUEST).enumerate() {
+            let (ids, messages): (Vec<_>, Vec<_>) = pending.iter().cloned().unzip();
+            let result = client
+                .send(&messages)
+                .and_then(|response| self.settle(&ids, &response).map(|_| response));
+            let offset = chunk * MAX_MESSAGES_PER_REQUEST;
+            results.push((offset..offset + ids.len(), result));
+        }
+
+        Response::combine(results)
+    }
+
+    /// Sends every pending message with `client` without blocking
+    /// the current thread on the injection requests. Accessing the
+    /// queue itself still blocks briefly. See `PersistentQueue::drain`.
+    #[cfg(all(feature = "async", any(feature = "reqwest", feature = "ureq")))]
+    pub async fn drain_async(&self, client: &SocketLabsClient) -> Result<Response<'static>> {
+        let mut results = Vec::new();
+        for (chunk, pending) in self.pending()?.chunks(MAX_MESSAGES_PER_REQUEST).enumerate() {
+            let (ids, messages): (Vec<_>, Vec<_>) = pending.iter().cloned().unzip();
+            let result = client
+                .send_async(&messages)
+                .await
+                .and_then(|response| self.settle(&ids, &response).map(|_| response));
+            let offset = chunk * MAX_MESSAGES_PER_REQUEST;
+            results.push((offset..offset + ids.len(), result));
+        }
+
+        Response::combine(results)
+    }
+
+    /// Removes the messages of an injection request
+    /// once SocketLabs accepted it.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn settle(&self, ids: &[u64], response: &Response) -> Result<()> {
+        match response.error_code {
+            PostMessageErrorCode::Success | PostMessageErrorCode::Warning => {}
+            error_code => return Err(ErrorKind::InjectionFailed(error_code).into()),
+        }
+
+        let mut batch = sled::Batch::default();
+        for id in ids {
+            batch.remove(&id.to_be_bytes());
+        }
+        self.db.apply_batch(batch)?;
+        self.db.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Decodes the id of a message from its key.
+fn id(key: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(key);
+    u64::from_be_bytes(bytes)
+}
@@ -5,14 +5,56 @@
 //! A representation of a request for
 //! the SocketLabs [Injection API](https://www.socketlabs.com/api-reference/injection-api/).
 
-use reqwest::{header::CONTENT_TYPE, Client};
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+use std::ops::Range;
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+use std::sync::OnceLock;
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+use std::time::Duration;
+
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+use bytes::Bytes;
 use serde_json;
 
-use error::Result;
-use message::Message;
-use response::Response;
+use crate::error::{ErrorKind, Result};
+use crate::message::{Message, MAX_RECIPIENTS_PER_MESSAGE};
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+use crate::response::Response;
+#[cfg(all(feature = "async", any(feature = "reqwest", feature = "ureq")))]
+use crate::retry::retry;
+#[cfg(all(feature = "blocking", any(feature = "reqwest", feature = "ureq")))]
+use crate::retry::retry_blocking;
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+use crate::retry::SendOptions;
+use crate::secret::SecretString;
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+use crate::transport::check_base_url;
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+use crate::transport::{default_transport, env_base_url, RawResponse};
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+use crate::transport::{ApiVersion, HttpOptions, Transport};
+
+/// The maximum number of messages accepted by
+/// SocketLabs in a single injection request.
+///
+/// Sending more messages splits them in several injection
+/// requests, whose responses are combined into one.
+pub const MAX_MESSAGES_PER_REQUEST: usize = 500;
 
-static API_URL: &'static str = "https://inject.socketlabs.com/api/v1/email";
+/// What the api key is replaced with in redacted request bodies.
+const REDACTED_API_KEY: &str = "<redacted>";
 
 /// This is the struct that will hold
 /// all  tokens needed for
@@ -22,30 +64,440 @@ static API_URL: &'static str = "https://inject.socketlabs.com/api/v1/email";
 #[serde(rename_all = "PascalCase")]
 pub struct Request<'a> {
     server_id: u16,
-    api_key: String,
+    api_key: SecretString,
     messages: Vec<Message<'a>>,
+    /// Whether to validate every message before sending.
+    #[serde(skip)]
+    validate: bool,
+    /// Whether to remove duplicated recipients before sending.
+    #[serde(skip)]
+    dedupe_recipients: bool,
+    /// How failed injection requests are retried.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    #[serde(skip)]
+    options: SendOptions,
+    /// Overrides the base URL of the Injection API.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    #[serde(skip)]
+    base_url: Option<String>,
+    /// The revision of the injection endpoint.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    #[serde(skip)]
+    api_version: ApiVersion,
+    /// Options of the HTTP clients the request is sent with.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    #[serde(skip)]
+    http_options: HttpOptions,
+    /// Built on the first send and kept, so that sending the
+    /// request again reuses the same pooled connections.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    #[serde(skip)]
+    transport: OnceLock<Box<dyn Transport>>,
+}
+
+/// The body of a single injection request,
+/// borrowing the messages to send.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct Injection<'a, 'b> {
+    pub(crate) server_id: u16,
+    pub(crate) api_key: &'b str,
+    pub(crate) messages: &'b [Message<'a>],
 }
 
 impl<'a> Request<'a> {
     /// Creates a new request object with
     /// the given credentials and messages.
-    pub fn new(server_id: u16, api_key: String, messages: Vec<Message<'a>>) -> Result<Request> {
+    pub fn new(server_id: u16, api_key: String, messages: Vec<Message<'a>>) -> Result<Request<'a>> {
         Ok(Request {
-            server_id: server_id,
-            api_key: api_key,
-            messages: messages,
+            server_id,
+            api_key: SecretString::new(api_key),
+            messages,
+            validate: false,
+            dedupe_recipients: false,
+            #[cfg(any(feature = "reqwest", feature = "ureq"))]
+            options: SendOptions::default(),
+            #[cfg(any(feature = "reqwest", feature = "ureq"))]
+            base_url: None,
+            #[cfg(any(feature = "reqwest", feature = "ureq"))]
+            api_version: ApiVersion::default(),
+            #[cfg(any(feature = "reqwest", feature = "ureq"))]
+            http_options: HttpOptions::default(),
+            #[cfg(any(feature = "reqwest", feature = "ureq"))]
+            transport: OnceLock::new(),
         })
     }
 
+    /// Sets whether every message is checked with `Message::validate`
+    /// before sending, failing locally instead of at SocketLabs.
+    pub fn set_validate(&mut self, validate: bool) {
+        self.validate = validate
+    }
+
+    /// Sets whether duplicated recipients are removed from every
+    /// message before sending. See `Message::dedupe_recipients`.
+    pub fn set_dedupe_recipients(&mut self, dedupe_recipients: bool) {
+        self.dedupe_recipients = dedupe_recipients
+    }
+
+    /// Sets how injection requests failing with
+    /// a transient error are retried.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    pub fn set_send_options(&mut self, options: SendOptions) {
+        self.options = options
+    }
+
+    /// Sets the base URL of the Injection API, instead of the one from
+    /// the `transport::BASE_URL_VAR` environment variable or the default.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    pub fn set_base_url<T: Into<String>>(&mut self, base_url: T) -> Result<()> {
+        let base_url = base_url.into();
+        check_base_url(&base_url)?;
+        self.base_url = Some(base_url);
+        self.transport.take();
+
+        Ok(())
+    }
+
+    /// Sets the revision of the injection endpoint
+    /// the request is sent to. Defaults to `ApiVersion::V1`.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    pub fn set_api_version(&mut self, api_version: ApiVersion) {
+        self.api_version = api_version;
+        self.transport.take();
+    }
+
+    /// Sets how long connecting to SocketLabs may take.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    pub fn set_connect_timeout(&mut self, connect_timeout: Duration) {
+        self.http_options.connect_timeout = Some(connect_timeout);
+        self.transport.take();
+    }
+
+    /// Sets how long every injection request may take as a whole,
+    /// retries aside. Requests taking longer fail with `ErrorKind::Timeout`.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.http_options.timeout = Some(timeout);
+        self.transport.take();
+    }
+
+    /// Adds an HTTP header sent with every injection request, such as
+    /// a correlation id. These aren't email headers, see
+    /// `Message::add_header` for those.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    pub fn add_http_header<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) {
+        self.http_options.headers.push((name.into(), value.into()));
+        self.transport.take();
+    }
+
+    /// Checks every message with `Message::validate`, returning
+    /// the problems of the first invalid message found.
+    pub fn validate(&self) -> Result<()> {
+        validate_messages(&self.messages)
+    }
+
+    /// The transport to send the injection requests with, built on first use.
+    #[cfg(all(
+        any(feature = "async", feature = "blocking"),
+        any(feature = "reqwest", feature = "ureq")
+    ))]
+    fn transport(&self) -> Result<&dyn Transport> {
+        if let Some(transport) = self.transport.get() {
+            return Ok(transport.as_ref());
+        }
+
+        let base_url = self.base_url.clone().unwrap_or_else(env_base_url);
+        let transport = default_transport(base_url, self.api_version, &self.http_options)?;
+        Ok(self.transport.get_or_init(|| transport).as_ref())
+    }
+
+    /// The body of every injection request `Request::send` would post,
+    /// one per `MAX_MESSAGES_PER_REQUEST` messages, once duplicated
+    /// recipients are removed and messages validated as configured.
+    ///
+    /// With `redact_api_key`, the api key is replaced by `<redacted>`,
+    /// such as for bodies which are logged or stored.
+    pub fn body_bytes(&self, redact_api_key: bool) -> Result<Vec<Vec<u8>>> {
+        let api_key = if redact_api_key {
+            REDACTED_API_KEY
+        } else {
+            self.api_key.expose_secret()
+        };
+        let bodies = self.serialize(api_key)?;
+
+        Ok(bodies.into_iter().map(|(_, body)| body).collect())
+    }
+
+    /// The body of every injection request `Request::send`
+    /// would post, as JSON. See `Request::body_bytes`.
+    pub fn to_json(&self, redact_api_key: bool) -> Result<Vec<String>> {
+        self.body_bytes(redact_api_key)?
+            .into_iter()
+            .map(|body| String::from_utf8(body).map_err(|_| ErrorKind::UnexpectedError.into()))
+            .collect()
+    }
+
+    /// Applies the sending options and serializes the body of every
+    /// injection request needed to send the messages with `transport`.
+    #[cfg(all(
+        any(feature = "async", feature = "blocking"),
+        any(feature = "reqwest", feature = "ureq")
+    ))]
+    #[cfg_attr(not(feature = "logging"), allow(unused_variables))]
+    fn bodies(&self, transport: &dyn Transport) -> Result<Vec<(usize, Vec<u8>)>> {
+        log_debug!(
+            "Sending {} message(s) to {} (server_id: {}, api_key: <redacted>)",
+            self.messages.len(),
+            transport
+                .endpoint()
+                .as_deref()
+                .unwrap_or("an unknown endpoint"),
+            self.server_id
+        );
+
+        self.serialize(self.api_key.expose_secret())
+    }
+
+    /// Serializes the body of every injection request needed to
+    /// send the messages along with `api_key`, once the sending
+    /// options are applied.
+    fn serialize(&self, api_key: &str) -> Result<Vec<(usize, Vec<u8>)>> {
+        let deduped: Vec<Message>;
+        let messages = if self.dedupe_recipients {
+            deduped = self
+                .messages
+                .iter()
+                .cloned()
+                .map(|mut message| {
+                    message.dedupe_recipients();
+                    message
+                })
+                .collect();
+            &deduped
+        } else {
+            &self.messages
+        };
+
+        if self.validate {
+            validate_messages(messages)?;
+        }
+
+        injection_bodies(self.server_id, api_key, messages)
+    }
+
+    /// The range of the messages sent in the
+    /// injection request starting at `offset`.
+    #[cfg(all(
+        any(feature = "async", feature = "blocking"),
+        any(feature = "reqwest", feature = "ureq")
+    ))]
+    fn chunk(&self, offset: usize) -> Range<usize> {
+        offset..self.messages.len().min(offset + MAX_MESSAGES_PER_REQUEST)
+    }
+
     /// Sends an email using the  Injection API
-    pub fn send(&self) -> Result<Response> {
-        let body = serde_json::to_string(&self)?;
-        let client = Client::new();
-        let mut response = client
-            .post(API_URL)
-            .header(CONTENT_TYPE, "application/json")
-            .body(body)
-            .send()?;
-        serde_json::from_str::<Response>(&response.text()?).map_err(From::from)
+    ///
+    /// Fails with `ErrorKind::PartiallySent` when only some of
+    /// the injection requests the messages are split in fail.
+    #[cfg(all(feature = "blocking", any(feature = "reqwest", feature = "ureq")))]
+    pub fn send(&self) -> Result<Response<'_>> {
+        let transport = self.transport()?;
+        let mut results = Vec::new();
+        for (offset, body) in self.bodies(transport)? {
+            let body = Bytes::from(body);
+            let result = retry_blocking(&self.options, || post_blocking(transport, &body));
+            results.push((self.chunk(offset), result));
+        }
+
+        Response::combine(results)
+    }
+
+    /// Sends an email using the Injection API
+    /// without blocking the current thread.
+    ///
+    /// The injection requests the messages are split in are sent one
+    /// after the other. `SocketLabsClient::send_async` can send them
+    /// concurrently, see `SocketLabsClient::set_max_in_flight`.
+    ///
+    /// This must be awaited from within a Tokio runtime.
+    #[cfg(all(feature = "async", any(feature = "reqwest", feature = "ureq")))]
+    pub async fn send_async(&self) -> Result<Response<'_>> {
+        let transport = self.transport()?;
+        let mut results = Vec::new();
+        for (offset, body) in self.bodies(transport)? {
+            let body = Bytes::from(body);
+            let result = retry(&self.options, || post(transport, &body)).await;
+            results.push((self.chunk(offset), result));
+        }
+
+        Response::combine(results)
+    }
+}
+
+/// Checks every message with `Message::validate`, returning
+/// the problems of the first invalid message found.
+pub(crate) fn validate_messages(messages: &[Message]) -> Result<()> {
+    for (index, message) in messages.iter().enumerate() {
+        message
+            .validate()
+            .map_err(|errors| ErrorKind::InvalidMessage(index, errors))?;
+    }
+
+    Ok(())
+}
+
+/// Checks the messages against the SocketLabs limits
+/// on messages per request and recipients per message.
+pub(crate) fn check_limits(messages: &[Message]) -> Result<()> {
+    if messages.len() > MAX_MESSAGES_PER_REQUEST {
+        return Err(ErrorKind::TooManyMessages(messages.len()).into());
+    }
+
+    check_recipients(messages)
+}
+
+/// Checks the messages against the SocketLabs
+/// limit on recipients per message.
+pub(crate) fn check_recipients(messages: &[Message]) -> Result<()> {
+    for (index, message) in messages.iter().enumerate() {
+        let recipients = message.recipient_count();
+        if recipients > MAX_RECIPIENTS_PER_MESSAGE {
+            return Err(ErrorKind::TooManyRecipients(index, recipients).into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Serializes the messages in as many injection request bodies as
+/// needed to stay under `MAX_MESSAGES_PER_REQUEST`, each along with
+/// the index of its first message.
+pub(crate) fn injection_bodies(
+    server_id: u16,
+    api_key: &str,
+    messages: &[Message],
+) -> Result<Vec<(usize, Vec<u8>)>> {
+    check_recipients(messages)?;
+
+    // An empty request is still sent, so that SocketLabs reports it.
+    if messages.is_empty() {
+        let injection = Injection {
+            server_id,
+            api_key,
+            messages,
+        };
+        return Ok(vec![(0, serde_json::to_vec(&injection)?)]);
+    }
+
+    messages
+        .chunks(MAX_MESSAGES_PER_REQUEST)
+        .enumerate()
+        .map(|(chunk, messages)| {
+            let injection = Injection {
+                server_id,
+                api_key,
+                messages,
+            };
+            Ok((
+                chunk * MAX_MESSAGES_PER_REQUEST,
+                serde_json::to_vec(&injection)?,
+            ))
+        })
+        .collect()
+}
+
+/// Sends a single injection request.
+#[cfg(all(feature = "blocking", any(feature = "reqwest", feature = "ureq")))]
+pub(crate) fn post_blocking(transport: &dyn Transport, body: &Bytes) -> Result<Response<'static>> {
+    let result = transport.execute(body.clone()).and_then(parse);
+    log_outcome(&result);
+    result
+}
+
+/// Sends a single injection request without blocking the current thread.
+#[cfg(all(feature = "async", any(feature = "reqwest", feature = "ureq")))]
+pub(crate) async fn post(transport: &dyn Transport, body: &Bytes) -> Result<Response<'static>> {
+    let result = transport.execute_async(body.clone()).await.and_then(parse);
+    log_outcome(&result);
+    result
+}
+
+/// Reads the injection response out of an HTTP response.
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+fn parse(raw: RawResponse) -> Result<Response<'static>> {
+    let retry_after = raw.header("Retry-After").and_then(retry_after);
+    check_status(raw.status, retry_after)?;
+    let mut response = serde_json::from_slice::<Response>(&raw.body)?.into_owned();
+    response.retry_after = retry_after;
+
+    Ok(response)
+}
+
+/// Logs how an injection request ended, whether
+/// SocketLabs responded or the request failed.
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+#[cfg_attr(not(feature = "logging"), allow(unused_variables))]
+fn log_outcome(result: &Result<Response<'static>>) {
+    match result {
+        Ok(response) => {
+            log_debug!("SocketLabs responded with {:?}", response.error_code);
+        }
+        Err(error) => {
+            log_debug!("The injection request failed: {}", error);
+        }
+    }
+}
+
+/// Fails on the HTTP statuses with which SocketLabs
+/// doesn't respond with an injection response.
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+fn check_status(status: u16, retry_after: Option<Duration>) -> Result<()> {
+    if status == 429 {
+        return Err(ErrorKind::RateLimited(retry_after).into());
+    }
+    if (500..600).contains(&status) {
+        return Err(ErrorKind::HttpError(status).into());
+    }
+
+    Ok(())
+}
+
+/// Reads a `Retry-After` header given in seconds. HTTP dates
+/// are ignored, falling back to the retry backoff.
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+fn retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// Parses the body of a request, redacting its api key.
+#[cfg(all(
+    any(
+        all(feature = "logging", any(feature = "async", feature = "blocking")),
+        feature = "testing"
+    ),
+    any(feature = "reqwest", feature = "ureq")
+))]
+pub(crate) fn redact(body: &[u8]) -> serde_json::Value {
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(mut request) => {
+            if let Some(api_key) = request.get_mut("ApiKey") {
+                *api_key = serde_json::Value::from(REDACTED_API_KEY);
+            }
+            request
+        }
+        Err(_) => serde_json::Value::from(String::from_utf8_lossy(body).into_owned()),
     }
 }
@@ -6,11 +6,28 @@
 //! the SocketLabs [Injection API](https://www.socketlabs.com/api-reference/injection-api/).
 
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::time::Duration;
 
 use serde::de::{Deserialize, Deserializer};
+use serde_json::Value;
+
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+use crate::error;
+use crate::error::ErrorKind;
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+use crate::message::Message;
 
 /// Representation of the SocketLabs AddressResult.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct AddressResult<'a> {
     /// The recipient address which generated the warning or error.
@@ -25,12 +42,12 @@ pub struct AddressResult<'a> {
 }
 
 /// Representation of the SocketLabs MessageResult.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct MessageResult<'a> {
     /// The index of the message that this response represents
     /// from the original array posted.
-    pub index: u16,
+    pub index: usize,
     /// The reason for message delivery failure when an error
     /// occurs on the message-level.
     #[serde(deserialize_with = "deserialize_messageresult")]
@@ -41,7 +58,7 @@ pub struct MessageResult<'a> {
 }
 
 /// Representation of the SocketLabs PostResponse.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Response<'a> {
     /// The success or failure details of the overall injection request.
@@ -54,12 +71,329 @@ pub struct Response<'a> {
     /// An array of message result objects for messages that failed or
     /// have bad recipients. If there were no errors this response is empty.
     pub message_results: Option<Vec<MessageResult<'a>>>,
+    /// Any field of the response not modeled by this struct.
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+    /// How long SocketLabs asked to wait before sending
+    /// again, from the `Retry-After` header, if any.
+    #[serde(skip)]
+    pub retry_after: Option<Duration>,
+}
+
+/// The outcome of a message accepted by SocketLabs,
+/// as yielded by `SocketLabsClient::send_all`.
+#[derive(Debug, Default)]
+pub struct MessageOutcome {
+    /// The recipients of the message which SocketLabs rejected, if any.
+    pub rejected_addresses: Vec<AddressResult<'static>>,
+}
+
+/// What was sent of messages split in several injection
+/// requests, when only some of those requests failed.
+#[derive(Clone, Debug)]
+pub struct PartialResponse {
+    /// The combined response to the injection requests which went
+    /// through, whose message results point to the original positions.
+    pub response: Response<'static>,
+    /// The injection requests which failed, as the range
+    /// of the messages they held along with their error.
+    pub failed: Vec<(Range<usize>, ErrorKind)>,
+}
+
+impl fmt::Display for PartialResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} injection request(s) failed", self.failed.len())
+    }
+}
+
+/// A response which owns all of its data, so it can
+/// be stored or moved across threads freely.
+pub type OwnedResponse = Response<'static>;
+
+impl<'a> AddressResult<'a> {
+    /// Converts into a result which owns all of its data.
+    pub fn into_owned(self) -> AddressResult<'static> {
+        AddressResult {
+            email_address: Cow::Owned(self.email_address.into_owned()),
+            accepted: self.accepted,
+            error_code: self.error_code,
+        }
+    }
+}
+
+impl<'a> MessageResult<'a> {
+    /// Converts into a result which owns all of its data.
+    pub fn into_owned(self) -> MessageResult<'static> {
+        MessageResult {
+            index: self.index,
+            error_code: self.error_code,
+            address_result: self
+                .address_result
+                .map(|results| results.into_iter().map(AddressResult::into_owned).collect()),
+        }
+    }
+}
+
+impl<'a> Response<'a> {
+    /// Converts into a response which owns all of its data.
+    pub fn into_owned(self) -> OwnedResponse {
+        Response {
+            error_code: self.error_code,
+            transaction_receipt: self
+                .transaction_receipt
+                .map(|receipt| Cow::Owned(receipt.into_owned())),
+            message_results: self
+                .message_results
+                .map(|results| results.into_iter().map(MessageResult::into_owned).collect()),
+            extra: self.extra,
+            retry_after: self.retry_after,
+        }
+    }
+}
+
+impl<'a> Response<'a> {
+    /// The indices of the messages which SocketLabs rejected, either
+    /// as a whole or for some of their recipients, whose addresses
+    /// are then listed in the `address_result` of the message.
+    ///
+    /// When the request failed as a whole, such as with `OverQuota`,
+    /// SocketLabs doesn't list its messages, see `error_code` instead.
+    pub fn failed_indices(&self) -> Vec<usize> {
+        let mut indices: Vec<_> = self
+            .message_results
+            .iter()
+            .flatten()
+            .filter(|result| {
+                result.error_code != MessageResultErrorCode::Warning
+                    || result
+                        .address_result
+                        .iter()
+                        .flatten()
+                        .any(|address| !address.accepted)
+            })
+            .map(|result| result.index)
+            .collect();
+        indices.sort_unstable();
+        indices
+    }
+
+    /// Rebuilds the messages which failed with a retryable error
+    /// code, along with their indices in `messages`. Messages with
+    /// some bad recipients only keep the ones worth sending to again,
+    /// the first of them moved to To when none of the To ones failed.
+    #[cfg(all(
+        any(feature = "async", feature = "blocking"),
+        any(feature = "reqwest", feature = "ureq")
+    ))]
+    pub(crate) fn failed_batch<'m>(
+        &self,
+        messages: &[Message<'m>],
+    ) -> (Vec<usize>, Vec<Message<'m>>) {
+        let mut batch = (Vec::new(), Vec::new());
+        for result in self.message_results.iter().flatten() {
+            let message = match messages.get(result.index) {
+                Some(message) => message,
+                None => continue,
+            };
+            if result.error_code.is_retryable() {
+                batch.0.push(result.index);
+                batch.1.push(message.clone());
+                continue;
+            }
+            if result.error_code != MessageResultErrorCode::Warning {
+                continue;
+            }
+
+            let failed: Vec<&str> = result
+                .address_result
+                .iter()
+                .flatten()
+                .filter(|address| !address.accepted && address.error_code.is_retryable())
+                .map(|address| &*address.email_address)
+                .collect();
+            if failed.is_empty() {
+                continue;
+            }
+            let is_failed = |email: &&str| {
+                failed
+                    .iter()
+                    .any(|failed| failed.eq_ignore_ascii_case(email))
+            };
+
+            let mut message = message.clone();
+            let sent: Vec<String> = message
+                .to()
+                .iter()
+                .chain(message.cc())
+                .chain(message.bcc())
+                .map(|email| email.address())
+                .filter(|address| !is_failed(address))
+                .map(str::to_string)
+                .collect();
+            for address in sent {
+                message.remove_to(&address);
+                message.remove_cc(&address);
+                message.remove_bcc(&address);
+            }
+            if message.to().is_empty() {
+                if let Some(email) = message.cc().iter().chain(message.bcc()).next().cloned() {
+                    message.remove_cc(email.address());
+                    message.remove_bcc(email.address());
+                    message.add_to(
+                        email.address().to_string(),
+                        email.friendly_name().map(str::to_string),
+                    );
+                }
+            }
+            batch.0.push(result.index);
+            batch.1.push(message);
+        }
+
+        batch
+    }
+}
+
+#[cfg(all(
+    any(feature = "async", feature = "blocking"),
+    any(feature = "reqwest", feature = "ureq")
+))]
+impl Response<'static> {
+    /// Points the message results of the response to a batch
+    /// built by `failed_batch` back to the original messages.
+    pub(crate) fn reindex(mut self, indices: &[usize]) -> Response<'static> {
+        for result in self.message_results.iter_mut().flatten() {
+            if let Some(&index) = indices.get(result.index) {
+                result.index = index;
+            }
+        }
+        self
+    }
+
+    /// Combines the results of the injection requests a larger request
+    /// was split in, each along with the range of its messages.
+    ///
+    /// Fails with the first error if every request failed, or with
+    /// `ErrorKind::PartiallySent` if only some did, so that the
+    /// responses of the requests which went through are not lost.
+    pub(crate) fn combine(
+        results: Vec<(Range<usize>, error::Result<Response<'static>>)>,
+    ) -> error::Result<Response<'static>> {
+        let mut responses = Vec::new();
+        let mut failed = Vec::new();
+        let mut first_error = None;
+        for (indices, result) in results {
+            match result {
+                Ok(response) => responses.push((indices.start, response)),
+                Err(error) => {
+                    failed.push((indices, error.kind().clone()));
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+
+        match first_error {
+            None => Ok(Response::aggregate(responses)),
+            Some(error) if responses.is_empty() => Err(error),
+            Some(_) => Err(ErrorKind::PartiallySent(Box::new(PartialResponse {
+                response: Response::aggregate(responses),
+                failed,
+            }))
+            .into()),
+        }
+    }
+
+    /// Combines the responses to the injection requests a larger
+    /// request was split in, each along with the index of its first
+    /// message, re-indexing message results to the original positions.
+    ///
+    /// The error code is the first one other than `Success` or
+    /// `Warning`, so that a request failing as a whole isn't
+    /// hidden, then `Warning` if any request had failed messages.
+    pub(crate) fn aggregate(responses: Vec<(usize, Response<'static>)>) -> Response<'static> {
+        let mut aggregated = Response {
+            error_code: PostMessageErrorCode::Success,
+            transaction_receipt: None,
+            message_results: None,
+            extra: HashMap::new(),
+            retry_after: None,
+        };
+
+        for (offset, response) in responses {
+            match (&aggregated.error_code, response.error_code) {
+                (_, PostMessageErrorCode::Success) => {}
+                (PostMessageErrorCode::Success, error_code)
+                | (PostMessageErrorCode::Warning, error_code) => aggregated.error_code = error_code,
+                _ => {}
+            }
+            if aggregated.transaction_receipt.is_none() {
+                aggregated.transaction_receipt = response.transaction_receipt;
+            }
+            if let Some(results) = response.message_results {
+                aggregated
+                    .message_results
+                    .get_or_insert_with(Vec::new)
+                    .extend(results.into_iter().map(|mut result| {
+                        result.index += offset;
+                        result
+                    }));
+            }
+            aggregated.retry_after = aggregated.retry_after.or(response.retry_after);
+            for (field, value) in response.extra {
+                aggregated.extra.entry(field).or_insert(value);
+            }
+        }
+
+        aggregated
+    }
+
+    /// Splits the response to an injection request into the outcome
+    /// of each of its messages, whose original indices are `indices`.
+    ///
+    /// When the request failed as a whole, every message
+    /// fails with `ErrorKind::InjectionFailed`.
+    #[cfg(feature = "async")]
+    pub(crate) fn outcomes(
+        self,
+        indices: Range<usize>,
+    ) -> Vec<(usize, error::Result<MessageOutcome>)> {
+        match self.error_code {
+            PostMessageErrorCode::Success | PostMessageErrorCode::Warning => {}
+            error_code => {
+                return indices
+                    .map(|index| (index, Err(ErrorKind::InjectionFailed(error_code).into())))
+                    .collect()
+            }
+        }
+
+        let mut outcomes: Vec<_> = indices
+            .map(|index| (index, Ok(MessageOutcome::default())))
+            .collect();
+        for result in self.message_results.unwrap_or_default() {
+            let outcome = match outcomes.get_mut(result.index) {
+                Some((_, outcome)) => outcome,
+                None => continue,
+            };
+            *outcome = match result.error_code {
+                MessageResultErrorCode::Warning => Ok(MessageOutcome {
+                    rejected_addresses: result
+                        .address_result
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(AddressResult::into_owned)
+                        .collect(),
+                }),
+                error_code => Err(ErrorKind::MessageRejected(error_code).into()),
+            };
+        }
+
+        outcomes
+    }
 }
 
 macro_rules! create_error_codes {
     ($(#[$docs:meta] ($enum:ident, $func: ident, ($(($kind:ident, $display:expr)),*) )),+) => ($(
         #[$docs]
-        #[derive(Debug, Deserialize, Fail, PartialEq)]
+        #[derive(Clone, Copy, Debug, Deserialize, Fail, PartialEq)]
         pub enum $enum {
             $(
                 #[fail(display = $display)]
@@ -118,3 +452,58 @@ create_error_codes! {
         ((InvalidAddress, "The address did not meet specification requirements."))
     )
 }
+
+impl PostMessageErrorCode {
+    /// Whether SocketLabs refused the credentials
+    /// or the account they belong to.
+    pub fn is_auth(self) -> bool {
+        matches!(
+            self,
+            PostMessageErrorCode::InvalidAuthentication
+                | PostMessageErrorCode::AccountDisabled
+                | PostMessageErrorCode::TooManyErrors
+        )
+    }
+}
+
+impl MessageResultErrorCode {
+    /// Whether sending the message again as it is may succeed.
+    ///
+    /// Every code SocketLabs documents for a single message is about
+    /// its content, so only `UnknownErrorCode` is retryable, as it may
+    /// stand for a transient code added to the API since. Throttling
+    /// and server errors, such as `OverQuota` or `InternalError`, fail
+    /// the request as a whole and are retried with it, see
+    /// `Error::is_retryable`. `Warning` messages are partly sent,
+    /// see `AddressResultErrorCode::is_retryable` for their recipients.
+    pub fn is_retryable(self) -> bool {
+        match self {
+            MessageResultErrorCode::UnknownErrorCode => true,
+            MessageResultErrorCode::Warning
+            | MessageResultErrorCode::InvalidAttachment
+            | MessageResultErrorCode::MessageTooLarge
+            | MessageResultErrorCode::EmptySubject
+            | MessageResultErrorCode::EmptyToAddress
+            | MessageResultErrorCode::InvalidFromAddress
+            | MessageResultErrorCode::NoValidBodyParts
+            | MessageResultErrorCode::NoValidRecipients
+            | MessageResultErrorCode::InvalidMergeData
+            | MessageResultErrorCode::InvalidTemplateId
+            | MessageResultErrorCode::MessageBodyConflict => false,
+        }
+    }
+}
+
+impl AddressResultErrorCode {
+    /// Whether sending to the address again may succeed.
+    ///
+    /// `InvalidAddress` is permanent, so only `UnknownErrorCode` is
+    /// retryable, as it may stand for a temporary failure of the
+    /// recipient added to the API since.
+    pub fn is_retryable(self) -> bool {
+        match self {
+            AddressResultErrorCode::UnknownErrorCode => true,
+            AddressResultErrorCode::InvalidAddress => false,
+        }
+    }
+}
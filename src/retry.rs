@@ -0,0 +1,133 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Retries of injection requests which failed with a transient error.
+
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "blocking")]
+use std::thread;
+use std::time::Duration;
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::error::{ErrorKind, Result};
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::response::{PostMessageErrorCode, Response};
+
+/// Options controlling how injection requests
+/// failing with a transient error are retried.
+///
+/// Network errors, 5xx and 429 HTTP responses and the
+/// `OverQuota` error code are considered transient. When
+/// SocketLabs rate limits a request with a `Retry-After`
+/// header, it is waited for instead of the backoff.
+#[derive(Clone, Debug)]
+pub struct SendOptions {
+    /// How many times a request is retried. Defaults to 0.
+    pub max_retries: u32,
+    /// The delay before the first retry, doubled on
+    /// every following one. Defaults to 1 second.
+    pub backoff: Duration,
+    /// The longest delay between two attempts. Defaults to 1 minute.
+    pub max_backoff: Duration,
+}
+
+impl Default for SendOptions {
+    fn default() -> SendOptions {
+        SendOptions {
+            max_retries: 0,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+impl SendOptions {
+    /// The delay before retry number `retry`, starting at 0.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .checked_mul(2u32.saturating_pow(retry))
+            .map_or(self.max_backoff, |delay| delay.min(self.max_backoff))
+    }
+}
+
+/// Whether the result of an attempt is worth retrying.
+#[cfg(any(feature = "async", feature = "blocking"))]
+pub(crate) fn is_transient(result: &Result<Response<'static>>) -> bool {
+    match result {
+        Ok(response) => response.error_code == PostMessageErrorCode::OverQuota,
+        Err(error) => error.is_retryable(),
+    }
+}
+
+/// How long SocketLabs asked to wait after an attempt, if at all.
+#[cfg(any(feature = "async", feature = "blocking"))]
+fn retry_after(result: &Result<Response<'static>>) -> Option<Duration> {
+    match result {
+        Ok(response) => response.retry_after,
+        Err(error) => match *error.kind() {
+            ErrorKind::RateLimited(retry_after) => retry_after,
+            _ => None,
+        },
+    }
+}
+
+/// Makes `attempt` until it succeeds, fails with a permanent error
+/// or runs out of retries, blocking the current thread in between.
+#[cfg(feature = "blocking")]
+pub(crate) fn retry_blocking<F>(options: &SendOptions, mut attempt: F) -> Result<Response<'static>>
+where
+    F: FnMut() -> Result<Response<'static>>,
+{
+    let mut retry = 0;
+    loop {
+        let result = attempt();
+        if retry >= options.max_retries || !is_transient(&result) {
+            return result;
+        }
+
+        log_debug!(
+            "Retrying injection request ({}/{})",
+            retry + 1,
+            options.max_retries
+        );
+        thread::sleep(retry_after(&result).unwrap_or_else(|| options.delay(retry)));
+        retry += 1;
+    }
+}
+
+/// Makes `attempt` until it succeeds, fails with a permanent error
+/// or runs out of retries, without blocking the current thread.
+#[cfg(feature = "async")]
+pub(crate) async fn retry<F, A>(options: &SendOptions, mut attempt: F) -> Result<Response<'static>>
+where
+    F: FnMut() -> A,
+    A: Future<Output = Result<Response<'static>>>,
+{
+    let mut retry = 0;
+    loop {
+        let result = attempt().await;
+        if retry >= options.max_retries || !is_transient(&result) {
+            return result;
+        }
+
+        log_debug!(
+            "Retrying injection request ({}/{})",
+            retry + 1,
+            options.max_retries
+        );
+        sleep(retry_after(&result).unwrap_or_else(|| options.delay(retry))).await;
+        retry += 1;
+    }
+}
+
+/// Waits for `duration` without blocking the current thread.
+#[cfg(feature = "async")]
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::time::sleep(duration).await;
+    #[cfg(target_arch = "wasm32")]
+    gloo_timers::future::sleep(duration).await;
+}
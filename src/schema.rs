@@ -0,0 +1,136 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The JSON Schema of the injection payload, along with a helper
+//! to check payloads built outside of this crate.
+
+use serde::de::IgnoredAny;
+use serde_json::{json, Value};
+
+use crate::error::{ErrorKind, Result};
+use crate::message::{Message, MAX_RECIPIENTS_PER_MESSAGE};
+use crate::request::{check_limits, validate_messages, MAX_MESSAGES_PER_REQUEST};
+
+/// An injection payload, as sent to SocketLabs.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Payload {
+    #[serde(rename = "ServerId")]
+    _server_id: IgnoredAny,
+    #[serde(rename = "ApiKey")]
+    _api_key: IgnoredAny,
+    messages: Vec<Message<'static>>,
+}
+
+/// The JSON Schema (draft-07) of the injection payload.
+///
+/// Only the shape of the payload is described, rules which span
+/// several fields, such as requiring a body or an api template,
+/// are only checked by `validate_json`.
+pub fn injection_schema() -> Value {
+    let string = json!({ "type": "string" });
+    let email = json!({
+        "type": "object",
+        "properties": {
+            "EmailAddress": string,
+            "FriendlyName": string,
+        },
+        "required": ["EmailAddress"],
+    });
+    let emails = json!({ "type": "array", "items": email });
+    let header = json!({
+        "type": "object",
+        "properties": {
+            "Name": string,
+            "Value": string,
+        },
+        "required": ["Name", "Value"],
+    });
+    let headers = json!({ "type": "array", "items": header });
+    let data = json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "Field": string,
+                "Value": string,
+            },
+            "required": ["Field", "Value"],
+        },
+    });
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "SocketLabs injection payload",
+        "type": "object",
+        "properties": {
+            "ServerId": { "type": "integer", "minimum": 0, "maximum": u16::MAX },
+            "ApiKey": string,
+            "Messages": {
+                "type": "array",
+                "minItems": 1,
+                "maxItems": MAX_MESSAGES_PER_REQUEST,
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "To": { "type": "array", "items": email, "maxItems": MAX_RECIPIENTS_PER_MESSAGE },
+                        "From": email,
+                        "Subject": string,
+                        "TextBody": string,
+                        "HtmlBody": string,
+                        "AmpBody": string,
+                        "ApiTemplate": string,
+                        "MailingId": string,
+                        "MessageId": string,
+                        "Charset": string,
+                        "CustomHeaders": headers,
+                        "Cc": emails,
+                        "Bcc": emails,
+                        "ReplyTo": email,
+                        "Attachment": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "Name": string,
+                                    "Content": { "type": "string", "contentEncoding": "base64" },
+                                    "ContentId": string,
+                                    "ContentType": string,
+                                    "CustomHeaders": headers,
+                                },
+                                "required": ["Name", "Content", "ContentType"],
+                            },
+                        },
+                        "MergeData": {
+                            "type": "object",
+                            "properties": {
+                                "PerMessage": { "type": "array", "items": data },
+                                "Global": data,
+                            },
+                        },
+                    },
+                    "required": ["From"],
+                },
+            },
+        },
+        "required": ["ServerId", "ApiKey", "Messages"],
+    })
+}
+
+/// Checks that `json` is an injection payload which
+/// SocketLabs would accept.
+///
+/// On top of the shape described by `injection_schema`, the
+/// request limits are checked and every message is validated
+/// as in `Request::validate`.
+pub fn validate_json(json: &str) -> Result<()> {
+    let payload: Payload = serde_json::from_str(json)?;
+    if payload.messages.is_empty() {
+        return Err(
+            ErrorKind::MessageParsingError("the payload holds no messages".to_string()).into(),
+        );
+    }
+    check_limits(&payload.messages)?;
+    validate_messages(&payload.messages)
+}
@@ -0,0 +1,63 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A `tower::Service` sending messages, so the client
+//! can be wrapped with existing tower middleware.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tower_service::Service;
+
+use crate::client::SocketLabsClient;
+use crate::error::{Error, Result};
+use crate::message::OwnedMessage;
+use crate::response::Response;
+
+/// A service sending batches of messages with a shared client,
+/// as `SocketLabsClient::send_async` does.
+///
+/// The service is always ready, limits such as timeouts or load
+/// shedding are left to middleware. Cloning it shares the client.
+#[derive(Clone, Debug)]
+pub struct SocketLabsService {
+    client: Arc<SocketLabsClient>,
+}
+
+impl SocketLabsService {
+    /// Creates a new service sending with `client`.
+    pub fn new<C: Into<Arc<SocketLabsClient>>>(client: C) -> SocketLabsService {
+        SocketLabsService {
+            client: client.into(),
+        }
+    }
+
+    /// The client messages are sent with.
+    pub fn client(&self) -> &SocketLabsClient {
+        &self.client
+    }
+}
+
+impl From<SocketLabsClient> for SocketLabsService {
+    fn from(client: SocketLabsClient) -> SocketLabsService {
+        SocketLabsService::new(client)
+    }
+}
+
+impl Service<Vec<OwnedMessage>> for SocketLabsService {
+    type Response = Response<'static>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<'static>>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, messages: Vec<OwnedMessage>) -> Self::Future {
+        let client = Arc::clone(&self.client);
+        Box::pin(async move { client.send_async(&messages).await })
+    }
+}
@@ -0,0 +1,97 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Local template rendering into message bodies, for
+//! users who don't rely on SocketLabs API templates.
+
+#[cfg(feature = "handlebars")]
+use handlebars::Handlebars;
+#[cfg(feature = "handlebars")]
+use serde::Serialize;
+#[cfg(feature = "tera")]
+use tera::{Context, Tera};
+
+use crate::error::{ErrorKind, Result};
+use crate::message::Message;
+
+/// Rendering with [Handlebars](https://handlebarsjs.com/) templates.
+#[cfg(feature = "handlebars")]
+impl<'a> Message<'a> {
+    /// Renders the Handlebars `template` with `context`
+    /// into the html_body field in the Message struct.
+    pub fn render_body<T: Serialize>(&mut self, template: &str, context: &T) -> Result<()> {
+        let html = Handlebars::new()
+            .render_template(template, context)
+            .map_err(|error| ErrorKind::TemplateError(error.to_string()))?;
+        self.set_html(html);
+        Ok(())
+    }
+
+    /// Renders the Handlebars `template` with `context`
+    /// into the text_body field in the Message struct.
+    ///
+    /// Unlike `Message::render_body`, values are not html escaped.
+    pub fn render_text_body<T: Serialize>(&mut self, template: &str, context: &T) -> Result<()> {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+        let text = handlebars
+            .render_template(template, context)
+            .map_err(|error| ErrorKind::TemplateError(error.to_string()))?;
+        self.set_text(text);
+        Ok(())
+    }
+}
+
+/// Rendering with [Tera](https://keats.github.io/tera/) templates.
+///
+/// Templates are looked up by name in an already loaded `Tera`
+/// instance, which decides whether values are html escaped.
+#[cfg(feature = "tera")]
+impl<'a> Message<'a> {
+    /// Renders the Tera template named `template` with `context`
+    /// into the html_body field in the Message struct.
+    pub fn render_tera_body(
+        &mut self,
+        tera: &Tera,
+        template: &str,
+        context: &Context,
+    ) -> Result<()> {
+        self.set_html(render_tera(tera, template, context)?);
+        Ok(())
+    }
+
+    /// Renders the Tera template named `template` with `context`
+    /// into the text_body field in the Message struct.
+    pub fn render_tera_text_body(
+        &mut self,
+        tera: &Tera,
+        template: &str,
+        context: &Context,
+    ) -> Result<()> {
+        self.set_text(render_tera(tera, template, context)?);
+        Ok(())
+    }
+
+    /// Renders the Tera template named `template` with `context`
+    /// into the subject field in the Message struct.
+    ///
+    /// Surrounding whitespace, such as a trailing newline
+    /// left by the template file, is trimmed.
+    pub fn render_tera_subject(
+        &mut self,
+        tera: &Tera,
+        template: &str,
+        context: &Context,
+    ) -> Result<()> {
+        let subject = render_tera(tera, template, context)?;
+        self.set_subject(subject.trim().to_string());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tera")]
+fn render_tera(tera: &Tera, template: &str, context: &Context) -> Result<String> {
+    tera.render(template, context)
+        .map_err(|error| ErrorKind::TemplateError(error.to_string()).into())
+}
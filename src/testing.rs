@@ -0,0 +1,379 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Helpers to test code sending messages, without any network access.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bytes::Bytes;
+use serde_json::{json, Value};
+
+use crate::error::{ErrorKind, Result};
+use crate::message::OwnedMessage;
+use crate::request::redact;
+use crate::response::{MessageResultErrorCode, PostMessageErrorCode, Response};
+#[cfg(feature = "async")]
+use crate::transport::TransportFuture;
+use crate::transport::{RawResponse, Transport};
+
+/// A transport recording every injection request and
+/// responding with canned responses, in the order they
+/// were queued. Once none is left, it responds with `Success`.
+///
+/// Since `Arc<MockTransport>` is a transport as well, the mock
+/// can be shared with a client and inspected after sending:
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use socketlabs::client::SocketLabsClient;
+/// # use socketlabs::message::Message;
+/// # use socketlabs::testing::MockTransport;
+/// let mock = Arc::new(MockTransport::new());
+/// let mut client = SocketLabsClient::new(1234, "api-key");
+/// client.set_transport(Arc::clone(&mock));
+///
+/// client.send(&[Message::new("foo@bar.com", None)]).unwrap();
+/// assert_eq!(mock.messages()[0].from().address(), "foo@bar.com");
+/// ```
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    responses: Mutex<VecDeque<std::result::Result<RawResponse, ErrorKind>>>,
+    requests: Mutex<Vec<Vec<u8>>>,
+}
+
+impl MockTransport {
+    /// Creates a new transport, responding with `Success` to every request.
+    pub fn new() -> MockTransport {
+        MockTransport::default()
+    }
+
+    /// Queues a raw HTTP response.
+    pub fn respond_with(&self, response: RawResponse) {
+        self.responses.lock().unwrap().push_back(Ok(response))
+    }
+
+    /// Queues an injection response with the given error
+    /// code, such as `OverQuota`, and no message results.
+    pub fn respond_with_error_code(&self, error_code: PostMessageErrorCode) {
+        self.respond_with(ResponseFixture::new(error_code).into_raw())
+    }
+
+    /// Queues a `Warning` injection response, in which SocketLabs
+    /// rejected the given addresses of the message at `index`.
+    pub fn respond_with_failed_addresses(&self, index: usize, addresses: &[&str]) {
+        let fixture = addresses
+            .iter()
+            .fold(ResponseFixture::warning(), |fixture, address| {
+                fixture.with_failed_address(index, *address)
+            });
+        self.respond_with(fixture.into_raw())
+    }
+
+    /// Queues an HTTP response with the given status and no body.
+    pub fn respond_with_status(&self, status: u16) {
+        self.respond_with(RawResponse {
+            status,
+            ..RawResponse::default()
+        })
+    }
+
+    /// Queues an error, such as a network error, failing the request.
+    pub fn fail_with(&self, error: ErrorKind) {
+        self.responses.lock().unwrap().push_back(Err(error))
+    }
+
+    /// The bodies of the injection requests sent so far.
+    pub fn requests(&self) -> Vec<Vec<u8>> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The messages of every injection request sent so far.
+    pub fn messages(&self) -> Vec<OwnedMessage> {
+        self.requests()
+            .iter()
+            .filter_map(|body| serde_json::from_slice::<Value>(body).ok())
+            .filter_map(|mut payload| serde_json::from_value(payload["Messages"].take()).ok())
+            .flat_map(|messages: Vec<OwnedMessage>| messages)
+            .collect()
+    }
+}
+
+impl Transport for MockTransport {
+    fn execute(&self, body: Bytes) -> Result<RawResponse> {
+        self.requests.lock().unwrap().push(body.to_vec());
+
+        match self.responses.lock().unwrap().pop_front() {
+            Some(response) => response.map_err(Into::into),
+            None => Ok(ResponseFixture::success().into_raw()),
+        }
+    }
+}
+
+/// Builds a `200 OK` HTTP response with the given JSON body.
+pub fn json_response(body: Value) -> RawResponse {
+    RawResponse {
+        status: 200,
+        headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+        body: body.to_string().into_bytes(),
+    }
+}
+
+/// Builds realistic injection responses, whose fields
+/// are otherwise only filled in by deserialization.
+///
+/// ```
+/// # use socketlabs::response::PostMessageErrorCode;
+/// # use socketlabs::testing::ResponseFixture;
+/// let response = ResponseFixture::warning()
+///     .with_failed_address(0, "bar@foo.com")
+///     .build();
+///
+/// assert_eq!(response.error_code, PostMessageErrorCode::Warning);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ResponseFixture {
+    error_code: PostMessageErrorCode,
+    transaction_receipt: Option<String>,
+    /// Message results, as JSON objects.
+    message_results: Vec<Value>,
+    retry_after: Option<Duration>,
+}
+
+impl ResponseFixture {
+    /// A response with the given error code and no message results.
+    pub fn new(error_code: PostMessageErrorCode) -> ResponseFixture {
+        ResponseFixture {
+            error_code,
+            transaction_receipt: None,
+            message_results: Vec::new(),
+            retry_after: None,
+        }
+    }
+
+    /// A response to an injection request whose messages were all sent.
+    pub fn success() -> ResponseFixture {
+        ResponseFixture::new(PostMessageErrorCode::Success)
+    }
+
+    /// A response to an injection request with failed messages
+    /// or recipients, see `with_failed_message` and `with_failed_address`.
+    pub fn warning() -> ResponseFixture {
+        ResponseFixture::new(PostMessageErrorCode::Warning)
+    }
+
+    /// A response to an injection request over the rate limit.
+    pub fn over_quota() -> ResponseFixture {
+        ResponseFixture::new(PostMessageErrorCode::OverQuota)
+    }
+
+    /// Sets the transaction receipt.
+    pub fn with_transaction_receipt<T: Into<String>>(mut self, receipt: T) -> Self {
+        self.transaction_receipt = Some(receipt.into());
+        self
+    }
+
+    /// Adds the result of a message SocketLabs rejected.
+    pub fn with_failed_message(mut self, index: usize, error_code: MessageResultErrorCode) -> Self {
+        self.message_results.push(json!({
+            "Index": index,
+            "ErrorCode": format!("{:?}", error_code),
+            "AddressResult": null,
+        }));
+        self
+    }
+
+    /// Adds a recipient SocketLabs rejected to the result of the
+    /// message at `index`, which only had bad recipients.
+    pub fn with_failed_address<T: Into<String>>(mut self, index: usize, address: T) -> Self {
+        let address = json!({
+            "EmailAddress": address.into(),
+            "Accepted": false,
+            "ErrorCode": "InvalidAddress",
+        });
+
+        match self
+            .message_results
+            .iter_mut()
+            .find(|result| result["Index"] == index && result["ErrorCode"] == "Warning")
+        {
+            Some(result) => match result["AddressResult"].as_array_mut() {
+                Some(addresses) => addresses.push(address),
+                None => result["AddressResult"] = json!([address]),
+            },
+            None => self.message_results.push(json!({
+                "Index": index,
+                "ErrorCode": "Warning",
+                "AddressResult": [address],
+            })),
+        }
+        self
+    }
+
+    /// Sets how long SocketLabs asks to wait before sending again.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    /// Builds the response, as deserialized by the client.
+    pub fn build(&self) -> Response<'static> {
+        let mut response: Response = serde_json::from_value(self.json())
+            .expect("fixtures always deserialize into a response");
+        response.retry_after = self.retry_after;
+        response
+    }
+
+    /// Builds the HTTP response SocketLabs would respond with,
+    /// such as for `MockTransport::respond_with`.
+    pub fn into_raw(self) -> RawResponse {
+        let mut raw = json_response(self.json());
+        if let Some(retry_after) = self.retry_after {
+            raw.headers
+                .push(("Retry-After".to_string(), retry_after.as_secs().to_string()));
+        }
+        raw
+    }
+
+    /// The JSON body of the response.
+    fn json(&self) -> Value {
+        json!({
+            "ErrorCode": format!("{:?}", self.error_code),
+            "TransactionReceipt": self.transaction_receipt,
+            "MessageResults": self.message_results,
+        })
+    }
+}
+
+/// A request and the response SocketLabs sent
+/// back, as stored by `RecordingTransport`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Interaction {
+    /// The JSON body of the request, with its api key redacted.
+    request: Value,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+/// Wraps a transport, recording every request along with its
+/// response in a JSON file, to replay them with `ReplayTransport`.
+///
+/// Api keys are redacted from the recorded requests.
+#[derive(Debug)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    path: PathBuf,
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    /// Records the requests sent with `inner` into the file
+    /// at `path`, overwriting it on every request.
+    pub fn new<P: Into<PathBuf>>(inner: T, path: P) -> RecordingTransport<T> {
+        RecordingTransport {
+            inner,
+            path: path.into(),
+            interactions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Stores a request along with its response.
+    fn record(&self, body: &[u8], raw: &RawResponse) -> Result<()> {
+        let mut interactions = self.interactions.lock().unwrap();
+        interactions.push(Interaction {
+            request: redact(body),
+            status: raw.status,
+            headers: raw.headers.clone(),
+            body: String::from_utf8_lossy(&raw.body).into_owned(),
+        });
+        fs::write(&self.path, serde_json::to_vec_pretty(&*interactions)?)?;
+
+        Ok(())
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn execute(&self, body: Bytes) -> Result<RawResponse> {
+        let raw = self.inner.execute(body.clone())?;
+        self.record(&body, &raw)?;
+        Ok(raw)
+    }
+
+    fn endpoint(&self) -> Option<String> {
+        self.inner.endpoint()
+    }
+
+    #[cfg(feature = "async")]
+    fn execute_async(&self, body: Bytes) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let raw = self.inner.execute_async(body.clone()).await?;
+            self.record(&body, &raw)?;
+            Ok(raw)
+        })
+    }
+}
+
+/// Responds with the responses recorded by `RecordingTransport`,
+/// in the order they were recorded, without any network access.
+///
+/// # Panics
+///
+/// When sending more requests than were recorded, or, unless
+/// disabled with `set_match_requests`, a request different from
+/// the recorded one, api keys aside.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    interactions: Mutex<VecDeque<Interaction>>,
+    match_requests: bool,
+}
+
+impl ReplayTransport {
+    /// Loads the interactions recorded in the file at `path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<ReplayTransport> {
+        let interactions: VecDeque<Interaction> = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(ReplayTransport {
+            interactions: Mutex::new(interactions),
+            match_requests: true,
+        })
+    }
+
+    /// Sets whether requests must match the recorded ones. Defaults to true.
+    pub fn set_match_requests(&mut self, match_requests: bool) {
+        self.match_requests = match_requests
+    }
+
+    /// How many recorded responses are left.
+    pub fn remaining(&self) -> usize {
+        self.interactions.lock().unwrap().len()
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn execute(&self, body: Bytes) -> Result<RawResponse> {
+        let interaction = self
+            .interactions
+            .lock()
+            .unwrap()
+            .pop_front()
+            .expect("no recorded response left to replay");
+        if self.match_requests {
+            assert_eq!(
+                redact(&body),
+                interaction.request,
+                "the request does not match the recorded one"
+            );
+        }
+
+        Ok(RawResponse {
+            status: interaction.status,
+            headers: interaction.headers,
+            body: interaction.body.into_bytes(),
+        })
+    }
+}
@@ -0,0 +1,697 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The HTTP transport injection requests are sent with.
+
+#[cfg(all(feature = "gzip", any(feature = "async", feature = "blocking")))]
+use std::io::Write;
+use std::sync::Arc;
+#[cfg(all(feature = "blocking", feature = "reqwest"))]
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::{env, fmt};
+
+use bytes::Bytes;
+#[cfg(all(feature = "gzip", any(feature = "async", feature = "blocking")))]
+use flate2::{write::GzEncoder, Compression};
+#[cfg(feature = "async")]
+use futures_util::future;
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+use futures_util::future::BoxFuture;
+#[cfg(all(feature = "async", target_arch = "wasm32"))]
+use futures_util::future::LocalBoxFuture;
+#[cfg(all(feature = "blocking", feature = "reqwest"))]
+use reqwest::blocking;
+#[cfg(feature = "reqwest")]
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+#[cfg(all(feature = "reqwest", any(feature = "async", feature = "blocking")))]
+use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE};
+#[cfg(all(feature = "async", feature = "reqwest"))]
+use reqwest::Client;
+#[cfg(all(
+    feature = "reqwest",
+    any(feature = "async", feature = "blocking"),
+    not(target_arch = "wasm32")
+))]
+use reqwest::Proxy;
+#[cfg(all(
+    any(feature = "default-tls", feature = "rustls-tls"),
+    not(target_arch = "wasm32")
+))]
+use reqwest::{tls, Certificate};
+#[cfg(any(feature = "async", feature = "blocking"))]
+use serde_json::json;
+
+use crate::error::{ErrorKind, Result};
+
+/// The base URL of the Injection API.
+pub const DEFAULT_BASE_URL: &str = "https://inject.socketlabs.com";
+
+/// The environment variable overriding the base URL of
+/// the Injection API, such as to point at a local mock server.
+pub const BASE_URL_VAR: &str = "SOCKETLABS_BASE_URL";
+
+/// The revisions of the injection endpoint.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ApiVersion {
+    /// The `/api/v1/email` endpoint.
+    #[default]
+    V1,
+}
+
+impl ApiVersion {
+    /// The path of the injection endpoint, relative to the base URL.
+    pub fn path(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/api/v1/email",
+        }
+    }
+}
+
+/// The HTTP response to an injection request,
+/// as returned by a `Transport`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RawResponse {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The response headers, as names and values.
+    pub headers: Vec<(String, String)>,
+    /// The response body.
+    pub body: Vec<u8>,
+}
+
+impl RawResponse {
+    /// The value of the first header named `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The future of an asynchronous injection request, which
+/// isn't `Send` on wasm32, where it runs on the event loop.
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+pub type TransportFuture<'a> = BoxFuture<'a, Result<RawResponse>>;
+
+/// The future of an asynchronous injection request, which
+/// isn't `Send` on wasm32, where it runs on the event loop.
+#[cfg(all(feature = "async", target_arch = "wasm32"))]
+pub type TransportFuture<'a> = LocalBoxFuture<'a, Result<RawResponse>>;
+
+/// Sends the JSON body of an injection request to
+/// SocketLabs, returning the raw HTTP response.
+///
+/// The body is handed over as `Bytes`, so that every attempt at
+/// the same request shares it instead of copying the payload.
+///
+/// `ReqwestTransport` is used by default, other implementations
+/// allow alternative HTTP backends, instrumentation or test
+/// doubles, see `SocketLabsClient::set_transport`.
+pub trait Transport: Send + Sync {
+    /// Sends an injection request, blocking the current thread.
+    fn execute(&self, body: Bytes) -> Result<RawResponse>;
+
+    /// The URL injection requests are sent to, if any, as logged
+    /// before sending. Defaults to `None`.
+    fn endpoint(&self) -> Option<String> {
+        None
+    }
+
+    /// Sends an injection request without blocking the current thread.
+    ///
+    /// Defaults to calling `execute`, which is only
+    /// fine for transports which never block.
+    #[cfg(feature = "async")]
+    fn execute_async(&self, body: Bytes) -> TransportFuture<'_> {
+        Box::pin(future::ready(self.execute(body)))
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn execute(&self, body: Bytes) -> Result<RawResponse> {
+        (**self).execute(body)
+    }
+
+    fn endpoint(&self) -> Option<String> {
+        (**self).endpoint()
+    }
+
+    #[cfg(feature = "async")]
+    fn execute_async(&self, body: Bytes) -> TransportFuture<'_> {
+        (**self).execute_async(body)
+    }
+}
+
+impl fmt::Debug for dyn Transport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Transport")
+    }
+}
+
+/// The versions of TLS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    V1_0,
+    V1_1,
+    V1_2,
+    V1_3,
+}
+
+#[cfg(all(
+    any(feature = "default-tls", feature = "rustls-tls"),
+    not(target_arch = "wasm32")
+))]
+impl From<TlsVersion> for tls::Version {
+    fn from(version: TlsVersion) -> tls::Version {
+        match version {
+            TlsVersion::V1_0 => tls::Version::TLS_1_0,
+            TlsVersion::V1_1 => tls::Version::TLS_1_1,
+            TlsVersion::V1_2 => tls::Version::TLS_1_2,
+            TlsVersion::V1_3 => tls::Version::TLS_1_3,
+        }
+    }
+}
+
+/// Options of the HTTP clients `ReqwestTransport` sends with.
+///
+/// On wasm32, only `headers` and `gzip_threshold` apply, since connections
+/// are made by the `fetch` API of the runtime.
+#[derive(Clone, Debug, Default)]
+pub struct HttpOptions {
+    /// The URL of a proxy every request goes through. Without it, the
+    /// proxies from the `HTTPS_PROXY`, `HTTP_PROXY` and `ALL_PROXY`
+    /// environment variables are used, unless `no_proxy` is set.
+    pub proxy: Option<String>,
+    /// Whether the proxies from the environment are ignored.
+    pub no_proxy: bool,
+    /// How long connecting to SocketLabs may take.
+    /// Defaults to no limit.
+    pub connect_timeout: Option<Duration>,
+    /// How long an injection request may take as a whole, from
+    /// connecting to reading the response. Defaults to no limit
+    /// when sending asynchronously and 30 seconds otherwise.
+    pub timeout: Option<Duration>,
+    /// PEM encoded certificates trusted on top of the system
+    /// ones, such as a corporate CA intercepting TLS. Each
+    /// entry may hold several certificates. Ignored without
+    /// a TLS backend feature.
+    pub root_certificates: Vec<Vec<u8>>,
+    /// The lowest version of TLS accepted. Defaults to the one
+    /// of the TLS backend. Ignored without a TLS backend feature.
+    pub min_tls_version: Option<TlsVersion>,
+    /// Whether requests are sent over HTTP/2 right away, instead
+    /// of negotiating the version of HTTP with SocketLabs.
+    pub http2_prior_knowledge: bool,
+    /// How long an idle connection is kept open for reuse.
+    /// Defaults to 90 seconds.
+    pub pool_idle_timeout: Option<Duration>,
+    /// How many idle connections are kept open
+    /// for reuse. Defaults to no limit.
+    pub pool_max_idle_per_host: Option<usize>,
+    /// The interval of TCP keep-alive probes on open
+    /// connections. Defaults to none being sent.
+    pub tcp_keepalive: Option<Duration>,
+    /// Extra HTTP headers, as names and values, sent with every
+    /// injection request, such as a correlation id or the headers
+    /// an egress gateway requires.
+    pub headers: Vec<(String, String)>,
+    /// Request bodies larger than this many bytes are sent
+    /// gzip compressed. Defaults to never compressing them.
+    #[cfg(feature = "gzip")]
+    pub gzip_threshold: Option<usize>,
+}
+
+/// Applies `HttpOptions` to either a blocking or an async client builder.
+#[cfg(all(
+    feature = "reqwest",
+    any(feature = "async", feature = "blocking"),
+    not(target_arch = "wasm32")
+))]
+macro_rules! configure {
+    ($builder:expr, $options:expr) => {{
+        let options: &HttpOptions = $options;
+        let mut builder = $builder;
+        if options.no_proxy {
+            builder = builder.no_proxy();
+        }
+        if let Some(connect_timeout) = options.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(timeout) = options.timeout {
+            builder = builder.timeout(timeout);
+        }
+        #[cfg(any(feature = "default-tls", feature = "rustls-tls"))]
+        {
+            for pem in &options.root_certificates {
+                let certificates = Certificate::from_pem_bundle(pem)
+                    .map_err(|error| ErrorKind::InvalidCertificate(error.to_string()))?;
+                for certificate in certificates {
+                    builder = builder.add_root_certificate(certificate);
+                }
+            }
+            if let Some(min_tls_version) = options.min_tls_version {
+                builder = builder.min_tls_version(min_tls_version.into());
+            }
+        }
+        if options.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        if let Some(pool_idle_timeout) = options.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(pool_idle_timeout);
+        }
+        if let Some(pool_max_idle_per_host) = options.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(pool_max_idle_per_host);
+        }
+        if let Some(tcp_keepalive) = options.tcp_keepalive {
+            builder = builder.tcp_keepalive(tcp_keepalive);
+        }
+        if let Some(ref proxy) = options.proxy {
+            let proxy =
+                Proxy::all(proxy.as_str()).map_err(|_| ErrorKind::InvalidUrl(proxy.clone()))?;
+            builder = builder.proxy(proxy);
+        }
+        builder.build()?
+    }};
+}
+
+/// The default transport, sending with `reqwest`.
+///
+/// HTTP connections are pooled and reused across requests.
+#[cfg(feature = "reqwest")]
+#[derive(Debug)]
+pub struct ReqwestTransport {
+    #[cfg(feature = "async")]
+    http: Client,
+    /// Only built on the first blocking request, since a blocking
+    /// client must never be created inside an async runtime.
+    #[cfg(feature = "blocking")]
+    blocking_http: OnceLock<blocking::Client>,
+    #[cfg(any(feature = "blocking", all(feature = "async", feature = "gzip")))]
+    options: HttpOptions,
+    /// The extra headers from the options.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    headers: HeaderMap,
+    /// The base URL of the Injection API, without a trailing slash.
+    base_url: String,
+    api_version: ApiVersion,
+}
+
+#[cfg(feature = "reqwest")]
+impl ReqwestTransport {
+    /// Creates a new transport, sending to the base URL from
+    /// the `BASE_URL_VAR` environment variable if set, or
+    /// to `DEFAULT_BASE_URL` otherwise.
+    pub fn new() -> ReqwestTransport {
+        ReqwestTransport::with_base_url(env_base_url())
+    }
+
+    /// Creates a new transport sending to the Injection API at
+    /// `base_url`, such as a gateway or a local mock server.
+    pub fn with_base_url<T: AsRef<str>>(base_url: T) -> ReqwestTransport {
+        ReqwestTransport {
+            #[cfg(feature = "async")]
+            http: Client::new(),
+            #[cfg(feature = "blocking")]
+            blocking_http: OnceLock::new(),
+            #[cfg(any(feature = "blocking", all(feature = "async", feature = "gzip")))]
+            options: HttpOptions::default(),
+            #[cfg(any(feature = "async", feature = "blocking"))]
+            headers: HeaderMap::new(),
+            base_url: base_url.as_ref().trim_end_matches('/').to_string(),
+            api_version: ApiVersion::default(),
+        }
+    }
+
+    /// Creates a new transport sending to the Injection API at
+    /// `base_url`, with HTTP clients configured by `options`.
+    pub fn with_options<T: AsRef<str>>(
+        base_url: T,
+        options: HttpOptions,
+    ) -> Result<ReqwestTransport> {
+        // Invalid headers fail here, even when nothing can be sent.
+        #[cfg(not(any(feature = "async", feature = "blocking")))]
+        header_map(&options.headers)?;
+
+        Ok(ReqwestTransport {
+            #[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+            http: configure!(Client::builder(), &options),
+            // The client of the `fetch` API has none of the options.
+            #[cfg(all(feature = "async", target_arch = "wasm32"))]
+            http: Client::new(),
+            #[cfg(any(feature = "async", feature = "blocking"))]
+            headers: header_map(&options.headers)?,
+            #[cfg(any(feature = "blocking", all(feature = "async", feature = "gzip")))]
+            options,
+            ..ReqwestTransport::with_base_url(base_url)
+        })
+    }
+
+    /// Sets the revision of the injection endpoint. Defaults to `ApiVersion::V1`.
+    pub fn set_api_version(&mut self, api_version: ApiVersion) {
+        self.api_version = api_version
+    }
+
+    /// Sets the HTTP client asynchronous requests are sent with, such
+    /// as one shared with the rest of an application. The `HttpOptions`
+    /// of the transport don't apply to it.
+    #[cfg(feature = "async")]
+    pub fn set_client(&mut self, http: Client) {
+        self.http = http
+    }
+
+    /// Sets the HTTP client blocking requests are sent with, instead
+    /// of one built from the `HttpOptions` of the transport.
+    #[cfg(feature = "blocking")]
+    pub fn set_blocking_client(&mut self, http: blocking::Client) {
+        self.blocking_http = OnceLock::from(http)
+    }
+
+    /// The URL of the injection endpoint.
+    pub fn url(&self) -> String {
+        format!("{}{}", self.base_url, self.api_version.path())
+    }
+
+    /// The blocking HTTP client, built on first use.
+    #[cfg(feature = "blocking")]
+    fn blocking_http(&self) -> Result<&blocking::Client> {
+        if let Some(http) = self.blocking_http.get() {
+            return Ok(http);
+        }
+
+        let http = configure!(blocking::Client::builder(), &self.options);
+        Ok(self.blocking_http.get_or_init(|| http))
+    }
+
+    /// The body to send, along with whether it is gzip compressed.
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn encode(&self, body: Bytes) -> Result<(Bytes, bool)> {
+        #[cfg(feature = "gzip")]
+        {
+            if let Some(threshold) = self.options.gzip_threshold {
+                if body.len() > threshold {
+                    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                    encoder.write_all(&body)?;
+                    return Ok((encoder.finish()?.into(), true));
+                }
+            }
+        }
+
+        Ok((body, false))
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl Default for ReqwestTransport {
+    fn default() -> ReqwestTransport {
+        ReqwestTransport::new()
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl Transport for ReqwestTransport {
+    fn endpoint(&self) -> Option<String> {
+        Some(self.url())
+    }
+
+    #[cfg(feature = "blocking")]
+    fn execute(&self, body: Bytes) -> Result<RawResponse> {
+        log_debug!("Posting an injection request to {}", self.url());
+        let (body, gzipped) = self.encode(body)?;
+        let mut request = self
+            .blocking_http()?
+            .post(self.url())
+            .headers(self.headers.clone())
+            .header(CONTENT_TYPE, "application/json");
+        if gzipped {
+            request = request.header(CONTENT_ENCODING, "gzip");
+        }
+        let response = request.body(body).send()?;
+
+        Ok(RawResponse {
+            status: response.status().as_u16(),
+            headers: headers(response.headers()),
+            body: response.bytes()?.to_vec(),
+        })
+    }
+
+    /// Without the `blocking` feature, there is no blocking client to
+    /// send the request with, so this fails with a `ConfigError`,
+    /// which is never retried.
+    #[cfg(not(feature = "blocking"))]
+    fn execute(&self, _body: Bytes) -> Result<RawResponse> {
+        Err(
+            ErrorKind::ConfigError("blocking requests need the `blocking` feature".to_string())
+                .into(),
+        )
+    }
+
+    #[cfg(feature = "async")]
+    fn execute_async(&self, body: Bytes) -> TransportFuture<'_> {
+        Box::pin(async move {
+            log_debug!("Posting an injection request to {}", self.url());
+            let (body, gzipped) = self.encode(body)?;
+            let mut request = self
+                .http
+                .post(self.url())
+                .headers(self.headers.clone())
+                .header(CONTENT_TYPE, "application/json");
+            if gzipped {
+                request = request.header(CONTENT_ENCODING, "gzip");
+            }
+            let response = request.body(body).send().await?;
+
+            Ok(RawResponse {
+                status: response.status().as_u16(),
+                headers: headers(response.headers()),
+                body: response.bytes().await?.to_vec(),
+            })
+        })
+    }
+}
+
+/// A lightweight transport, sending with `ureq`.
+///
+/// Requests are always blocking, sending asynchronously hands them
+/// to the blocking thread pool of the Tokio runtime. Only the proxy,
+/// timeouts and headers of `HttpOptions` apply to it.
+#[cfg(feature = "ureq")]
+#[derive(Debug)]
+pub struct UreqTransport {
+    agent: ureq::Agent,
+    /// Extra headers sent with every request.
+    headers: Vec<(String, String)>,
+    /// The base URL of the Injection API, without a trailing slash.
+    base_url: String,
+    api_version: ApiVersion,
+}
+
+#[cfg(feature = "ureq")]
+impl UreqTransport {
+    /// Creates a new transport, sending to the base URL from
+    /// the `BASE_URL_VAR` environment variable if set, or
+    /// to `DEFAULT_BASE_URL` otherwise.
+    pub fn new() -> UreqTransport {
+        UreqTransport::with_agent(env_base_url(), ureq::Agent::new_with_defaults())
+    }
+
+    /// Creates a new transport sending to the Injection API at
+    /// `base_url`, with an agent configured by `options`.
+    pub fn with_options<T: AsRef<str>>(
+        base_url: T,
+        options: &HttpOptions,
+    ) -> Result<UreqTransport> {
+        let mut config = ureq::Agent::config_builder()
+            .timeout_connect(options.connect_timeout)
+            .timeout_global(options.timeout);
+        if options.no_proxy {
+            config = config.proxy(None);
+        }
+        if let Some(ref proxy) = options.proxy {
+            let proxy =
+                ureq::Proxy::new(proxy).map_err(|_| ErrorKind::InvalidUrl(proxy.clone()))?;
+            config = config.proxy(Some(proxy));
+        }
+
+        Ok(UreqTransport {
+            headers: options.headers.clone(),
+            ..UreqTransport::with_agent(base_url, config.build().into())
+        })
+    }
+
+    /// Creates a new transport sending to the Injection API at
+    /// `base_url` with an existing agent.
+    pub fn with_agent<T: AsRef<str>>(base_url: T, agent: ureq::Agent) -> UreqTransport {
+        UreqTransport {
+            agent,
+            headers: Vec::new(),
+            base_url: base_url.as_ref().trim_end_matches('/').to_string(),
+            api_version: ApiVersion::default(),
+        }
+    }
+
+    /// Sets the revision of the injection endpoint. Defaults to `ApiVersion::V1`.
+    pub fn set_api_version(&mut self, api_version: ApiVersion) {
+        self.api_version = api_version
+    }
+
+    /// The URL of the injection endpoint.
+    pub fn url(&self) -> String {
+        format!("{}{}", self.base_url, self.api_version.path())
+    }
+}
+
+#[cfg(feature = "ureq")]
+impl Default for UreqTransport {
+    fn default() -> UreqTransport {
+        UreqTransport::new()
+    }
+}
+
+#[cfg(feature = "ureq")]
+impl Transport for UreqTransport {
+    fn endpoint(&self) -> Option<String> {
+        Some(self.url())
+    }
+
+    fn execute(&self, body: Bytes) -> Result<RawResponse> {
+        log_debug!("Posting an injection request to {}", self.url());
+        ureq_post(&self.agent, &self.url(), &self.headers, &body)
+    }
+
+    #[cfg(feature = "async")]
+    fn execute_async(&self, body: Bytes) -> TransportFuture<'_> {
+        log_debug!("Posting an injection request to {}", self.url());
+        let agent = self.agent.clone();
+        let url = self.url();
+        let headers = self.headers.clone();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || ureq_post(&agent, &url, &headers, &body))
+                .await
+                .map_err(|_| ErrorKind::UnexpectedError)?
+        })
+    }
+}
+
+/// Sends a single injection request with `ureq`.
+#[cfg(feature = "ureq")]
+fn ureq_post(
+    agent: &ureq::Agent,
+    url: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> Result<RawResponse> {
+    let mut request = agent.post(url).config().http_status_as_error(false).build();
+    for (name, value) in headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let mut response = request
+        .header("Content-Type", "application/json")
+        .send(body)?;
+
+    Ok(RawResponse {
+        status: response.status().as_u16(),
+        headers: response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body: response.body_mut().read_to_vec()?,
+    })
+}
+
+/// Responds with `Success` to every request without
+/// sending anything, for `ClientMode::DryRun`.
+#[cfg(any(feature = "async", feature = "blocking"))]
+#[derive(Debug)]
+pub(crate) struct DryRun;
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+impl Transport for DryRun {
+    fn execute(&self, _body: Bytes) -> Result<RawResponse> {
+        log_debug!("Dry run, not sending {}", crate::request::redact(&_body));
+
+        Ok(RawResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: json!({
+                "ErrorCode": "Success",
+                "TransactionReceipt": null,
+                "MessageResults": [],
+            })
+            .to_string()
+            .into_bytes(),
+        })
+    }
+}
+
+/// The base URL from the `BASE_URL_VAR` environment
+/// variable if set, or `DEFAULT_BASE_URL` otherwise.
+pub(crate) fn env_base_url() -> String {
+    env::var(BASE_URL_VAR).unwrap_or_else(|_| DEFAULT_BASE_URL.to_string())
+}
+
+/// Checks that a base URL is an absolute HTTP URL.
+#[cfg(feature = "reqwest")]
+pub(crate) fn check_base_url(base_url: &str) -> Result<()> {
+    match reqwest::Url::parse(base_url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(()),
+        _ => Err(ErrorKind::InvalidUrl(base_url.to_string()).into()),
+    }
+}
+
+/// Checks that a base URL is an absolute HTTP URL.
+#[cfg(all(feature = "ureq", not(feature = "reqwest")))]
+pub(crate) fn check_base_url(base_url: &str) -> Result<()> {
+    match base_url.parse::<ureq::http::Uri>() {
+        Ok(uri)
+            if matches!(uri.scheme_str(), Some("http") | Some("https")) && uri.host().is_some() =>
+        {
+            Ok(())
+        }
+        _ => Err(ErrorKind::InvalidUrl(base_url.to_string()).into()),
+    }
+}
+
+/// Builds the default transport: `ReqwestTransport`,
+/// or `UreqTransport` without the `reqwest` feature.
+#[cfg(any(feature = "async", feature = "blocking"))]
+pub(crate) fn default_transport(
+    base_url: String,
+    api_version: ApiVersion,
+    options: &HttpOptions,
+) -> Result<Box<dyn Transport>> {
+    #[cfg(feature = "reqwest")]
+    let mut transport = ReqwestTransport::with_options(base_url, options.clone())?;
+    #[cfg(not(feature = "reqwest"))]
+    let mut transport = UreqTransport::with_options(base_url, options)?;
+    transport.set_api_version(api_version);
+
+    Ok(Box::new(transport))
+}
+
+/// Converts extra headers, failing on invalid names or values.
+#[cfg(feature = "reqwest")]
+fn header_map(headers: &[(String, String)]) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let invalid = || ErrorKind::InvalidHttpHeader(name.clone());
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid())?;
+        let value = HeaderValue::from_str(value).map_err(|_| invalid())?;
+        map.append(name, value);
+    }
+
+    Ok(map)
+}
+
+/// Collects the headers whose value is valid text.
+#[cfg(all(feature = "reqwest", any(feature = "async", feature = "blocking")))]
+fn headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
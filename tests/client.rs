@@ -0,0 +1,97 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use socketlabs::client::SocketLabsClient;
+use socketlabs::error::ErrorKind;
+use socketlabs::message::Message;
+use socketlabs::response::{MessageResultErrorCode, PostMessageErrorCode};
+use socketlabs::retry::SendOptions;
+use socketlabs::testing::{MockTransport, RecordingTransport, ReplayTransport, ResponseFixture};
+
+fn client(mock: &Arc<MockTransport>) -> SocketLabsClient {
+    let mut client = SocketLabsClient::new(1234, "api-key");
+    client.set_transport(Arc::clone(mock));
+    client
+}
+
+#[test]
+fn send_records_the_injected_messages() {
+    let mock = Arc::new(MockTransport::new());
+    let messages = vec![Message::new("foo@bar.com", None)
+        .with_to("bar@foo.com", None)
+        .with_subject("Hello")];
+
+    let response = client(&mock).send(&messages).unwrap();
+
+    assert_eq!(response.error_code, PostMessageErrorCode::Success);
+    assert_eq!(mock.requests().len(), 1);
+    assert_eq!(mock.messages(), messages);
+}
+
+#[test]
+fn send_reports_failed_addresses() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond_with_failed_addresses(0, &["bar@foo.com"]);
+    let message = Message::new("foo@bar.com", None).with_to("bar@foo.com", None);
+
+    let response = client(&mock).send(&[message]).unwrap();
+
+    assert_eq!(response.error_code, PostMessageErrorCode::Warning);
+    let results = response.message_results.unwrap();
+    let addresses = results[0].address_result.as_ref().unwrap();
+    assert_eq!(addresses[0].email_address, "bar@foo.com");
+    assert!(!addresses[0].accepted);
+}
+
+#[test]
+fn send_retries_transient_failures() {
+    let mock = Arc::new(MockTransport::new());
+    mock.fail_with(ErrorKind::RequestError("connection reset".to_string()));
+    mock.respond_with_status(503);
+    mock.respond_with_error_code(PostMessageErrorCode::OverQuota);
+    let mut client = client(&mock);
+    client.set_send_options(SendOptions {
+        max_retries: 3,
+        backoff: Duration::ZERO,
+        ..Default::default()
+    });
+
+    let response = client.send(&[Message::new("foo@bar.com", None)]).unwrap();
+
+    assert_eq!(response.error_code, PostMessageErrorCode::Success);
+    assert_eq!(mock.requests().len(), 4);
+}
+
+#[test]
+fn fixture_lists_failed_messages() {
+    let response = ResponseFixture::warning()
+        .with_failed_message(2, MessageResultErrorCode::EmptySubject)
+        .with_failed_address(0, "bar@foo.com")
+        .with_failed_address(0, "baz@foo.com")
+        .build();
+
+    assert_eq!(response.failed_indices(), vec![0, 2]);
+    let results = response.message_results.unwrap();
+    assert_eq!(results[1].address_result.as_ref().unwrap().len(), 2);
+}
+
+#[test]
+fn replay_responds_like_the_recording() {
+    let path = std::env::temp_dir().join("socketlabs-replay-test.json");
+    let mock = MockTransport::new();
+    mock.respond_with_failed_addresses(0, &["bar@foo.com"]);
+    let messages = vec![Message::new("foo@bar.com", None).with_to("bar@foo.com", None)];
+
+    let mut recording = SocketLabsClient::new(1234, "api-key");
+    recording.set_transport(RecordingTransport::new(mock, &path));
+    let recorded = recording.send(&messages).unwrap();
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("api-key"));
+
+    let mut replaying = SocketLabsClient::new(1234, "another-api-key");
+    replaying.set_transport(ReplayTransport::from_path(&path).unwrap());
+    let replayed = replaying.send(&messages).unwrap();
+
+    assert_eq!(replayed.error_code, recorded.error_code);
+    assert_eq!(replayed.failed_indices(), recorded.failed_indices());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,62 @@
+extern crate log;
+extern crate socketlabs;
+
+use std::sync::Mutex;
+
+use log::{Level, Log, Metadata, Record};
+
+use socketlabs::message::Message;
+use socketlabs::request::Request;
+
+static API_KEY: &str = "super-secret-api-key";
+
+struct TestLogger {
+    records: Mutex<Vec<(Level, String)>>,
+}
+
+impl Log for TestLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.records
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: TestLogger = TestLogger {
+    records: Mutex::new(Vec::new()),
+};
+
+#[test]
+fn send_emits_debug_record_without_api_key() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let mut first = Message::new("foo@bar.com", None);
+    first.add_to("bar@foo.com", None);
+    let mut second = Message::new("foo@bar.com", None);
+    second.add_to("baz@foo.com", None);
+
+    let mut request = Request::new(1234, API_KEY.to_string(), vec![first, second]).unwrap();
+    // Nothing listens there, so the test never reaches SocketLabs.
+    request.set_base_url("http://127.0.0.1:9").unwrap();
+    // The request fails, which is logged after sending as well.
+    let _ = request.send();
+
+    let records = LOGGER.records.lock().unwrap();
+    assert!(records.iter().any(|(level, msg)| *level == Level::Debug
+        && msg.contains("Sending 2 message(s) to http://127.0.0.1:9/")));
+    assert!(
+        records
+            .iter()
+            .any(|(level, msg)| *level == Level::Debug
+                && msg.contains("The injection request failed"))
+    );
+    assert!(records.iter().all(|(_, msg)| !msg.contains(API_KEY)));
+}
@@ -0,0 +1,36 @@
+use socketlabs::message::{Message, Priority};
+
+#[test]
+fn message_round_trips_through_json() {
+    let mut message = Message::new("Foo <foo@bar.com>", None)
+        .with_to("bar@foo.com", Some("Bar"))
+        .with_cc("baz@foo.com", None)
+        .with_subject("Hello")
+        .with_text("Hello, text world!")
+        .with_html("<p>Hello, html world!</p>")
+        .with_header("X-Example", "hey")
+        .with_priority(Priority::High)
+        .with_per_message_merge(vec![("DeliveryAddress", "bar@foo.com"), ("Name", "Bar")])
+        .with_global_merge("Company", "Foo Inc.");
+    message.set_bulk_to(vec!["qux@foo.com"]);
+    message.add_attachment("hello.txt", "text/plain", b"Hello, attachment!");
+
+    let json = serde_json::to_string(&message).unwrap();
+    let parsed: Message = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(parsed, message);
+    assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+}
+
+#[test]
+fn message_deserializes_with_only_required_fields() {
+    let message: Message = serde_json::from_str(
+        r#"{"From": {"EmailAddress": "foo@bar.com"}, "To": [{"EmailAddress": "bar@foo.com"}]}"#,
+    )
+    .unwrap();
+
+    assert_eq!(message.from().address(), "foo@bar.com");
+    assert_eq!(message.to()[0].address(), "bar@foo.com");
+    assert_eq!(message.subject(), "");
+    assert!(message.html_body().is_none());
+}